@@ -1,16 +1,20 @@
 use std::{
     env::var,
-    fs::File,
-    io::Cursor,
     path::{Path, PathBuf},
 };
 
 use anyhow::Result;
-use zip::read::ZipArchive;
 
-fn download_from_zip(url: &str, file_name: &str, out_dir: &Path) -> Result<()> {
-    // check if out_dir already contains the file
-    if out_dir.join(file_name).exists() {
+#[allow(dead_code)]
+#[path = "src/assets.rs"]
+mod assets;
+use assets::Asset;
+
+fn download(asset: &Asset, out_dir: &Path) -> Result<()> {
+    let (url, file_name) = (&asset.url, &asset.file_name);
+    // the URL of the release the executable was extracted from, so that another version is downloaded again
+    let source_path = out_dir.join(format!("{}.url", file_name));
+    if out_dir.join(file_name).exists() && std::fs::read_to_string(&source_path).is_ok_and(|source| source == *url) {
         println!(
             "cargo:warning={} already exists in {}",
             file_name,
@@ -21,19 +25,19 @@ fn download_from_zip(url: &str, file_name: &str, out_dir: &Path) -> Result<()> {
 
     println!("cargo:warning=Downloading {}...", url);
     let response = reqwest::blocking::get(url)?;
-    let zip_file = Cursor::new(response.bytes()?);
-    println!("cargo:warning=Extracting {}...", file_name);
-    let mut zip = ZipArchive::new(zip_file)?;
-    let zip_file_path: String = zip
-        .file_names()
-        .find(|name| name.ends_with(file_name))
-        .ok_or_else(|| anyhow::anyhow!("{} not found in zip", file_name))?
-        .into();
-    let mut file = zip.by_name(&zip_file_path)?;
+    let bytes = response.error_for_status()?.bytes()?;
+    assets::verify_checksum(url, &bytes)?;
+    if !assets::is_pinned(url) {
+        println!(
+            "cargo:warning=No SHA-256 pinned for {}, its SHA-256 {} was not checked. Compare it with the checksums of the release and add it to CHECKSUMS in src/assets.rs",
+            url,
+            assets::sha256(&bytes)
+        );
+    }
     let out_path = out_dir.join(file_name);
-    println!("cargo:warning=Writing to {}...", out_path.to_string_lossy());
-    let mut out_file = File::create(&out_path)?;
-    std::io::copy(&mut file, &mut out_file)?;
+    println!("cargo:warning=Extracting {} to {}...", file_name, out_path.to_string_lossy());
+    assets::extract(asset, bytes.to_vec(), &out_path)?;
+    std::fs::write(source_path, url)?;
 
     Ok(())
 }
@@ -41,30 +45,42 @@ fn download_from_zip(url: &str, file_name: &str, out_dir: &Path) -> Result<()> {
 // src: https://stackoverflow.com/a/67516503/13565664
 fn get_output_path() -> PathBuf {
     //<root or manifest path>/target/<profile>/
+    // or target/<triple>/<profile>/ when cross-compiling with --target
     let manifest_dir_string = var("CARGO_MANIFEST_DIR").unwrap();
     let build_type = var("PROFILE").unwrap();
-    Path::new(&manifest_dir_string)
-        .join("target")
-        .join(build_type)
-        .into()
+    let target = var("TARGET").unwrap();
+    let mut path = Path::new(&manifest_dir_string).join("target");
+    if var("HOST").is_ok_and(|host| host != target) {
+        path = path.join(target);
+    }
+    path.join(build_type)
 }
 
 fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/assets.rs");
+    println!("cargo:rerun-if-env-changed=FD_VERSION");
+    println!("cargo:rerun-if-env-changed=FZF_VERSION");
+    println!("cargo:rerun-if-env-changed=BLINK_BUNDLE_TOOLS");
 
-    // only run on windows
-    if !cfg!(target_os = "windows") {
+    // fd and fzf are bundled with Windows builds, other targets download them only with BLINK_BUNDLE_TOOLS=1
+    let target = var("TARGET")?;
+    let bundle = match var("BLINK_BUNDLE_TOOLS") {
+        Ok(value) => value == "1",
+        Err(_) => target.contains("windows"),
+    };
+    if !bundle {
         return Ok(());
     }
 
     let out_dir = get_output_path();
     let out_dir = Path::new(&out_dir);
 
-    let fd_url = "https://github.com/sharkdp/fd/releases/download/v9.0.0/fd-v9.0.0-x86_64-pc-windows-msvc.zip";
-    let fzf_url = "https://github.com/junegunn/fzf/releases/download/0.46.1/fzf-0.46.1-windows_amd64.zip";
+    let fd_version = var("FD_VERSION").unwrap_or_else(|_| assets::DEFAULT_FD_VERSION.to_owned());
+    let fzf_version = var("FZF_VERSION").unwrap_or_else(|_| assets::DEFAULT_FZF_VERSION.to_owned());
 
-    download_from_zip(fd_url, "fd.exe", out_dir)?;
-    download_from_zip(fzf_url, "fzf.exe", out_dir)?;
+    download(&assets::fd_asset(&fd_version, &target)?, out_dir)?;
+    download(&assets::fzf_asset(&fzf_version, &target)?, out_dir)?;
 
     Ok(())
 }
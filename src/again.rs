@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use blink_search::config::{Config, Location};
+use blink_search::index::entry_path;
+use blink_search::{history, paths, recent, search, Opener};
+use crate::picker::{self, Choice, Pick};
+
+/// How many of the latest queries are resolved to entries.
+const QUERY_LIMIT: usize = 20;
+
+/// Entries of the location opened before: those of the `recent` list, then the best match of each query
+/// of the search history, latest first.
+fn entries(location_name: &str, location: &Location, config: &Config) -> Result<Vec<PathBuf>> {
+    // entries of groups are relative to their member, so only their history is used
+    let opened = std::fs::read_to_string(recent::path()).unwrap_or_default();
+    let opened = opened.lines()
+        .filter(|_| !location.path.is_empty())
+        .filter_map(|line| Path::new(line).strip_prefix(&location.path).ok().map(Path::to_owned));
+    let mut queries = history::queries(location_name)?;
+    queries.truncate(QUERY_LIMIT);
+    let matches = search::best_matches(location_name, location, config, &queries)?;
+
+    let mut entries: Vec<PathBuf> = Vec::new();
+    for entry in opened.chain(matches) {
+        if !entries.contains(&entry) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Shows the entries of the location opened before, and opens all chosen ones.
+pub fn open_again(location_name: &str, location: &Location, config: &Config) -> Result<()> {
+    let entries = entries(location_name, location, config)?;
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("Nothing opened in {} yet", location_name));
+    }
+    let lines = picker::lines(entries.iter().map(|e| e.to_string_lossy().into_owned()).collect());
+    let Choice::Entries(chosen) = picker::for_config(config).pick(Pick::Again { name: location_name }, lines)? else {
+        return Ok(());
+    };
+    let opener = Opener::new(config);
+    for entry in chosen {
+        opener.open_entry(&entry_path(location, &paths::from_bytes(entry), config), location)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use std::{ffi::OsString, io::{self, BufRead, BufReader, PipeReader, Write}, path::{Component, Path, PathBuf}, thread};
+use anyhow::Result;
+use log::{debug, warn};
+use crate::config::Location;
+use crate::paths;
+
+/// The extensions of the archives listed by `index_archives` locations.
+const EXTENSIONS: &[&str] = &[".zip", ".tar", ".tar.gz", ".tgz"];
+
+/// Whether the file name ends like a zip or tar archive.
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+/// Splits an entry like `backups/2023.zip!/photos/img001.jpg` into the archive and its member.
+/// Members which would leave the folder they are extracted to are refused.
+pub fn split_member(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    let mut archive = PathBuf::new();
+    let mut components = path.components();
+    for component in components.by_ref() {
+        let name = component.as_os_str().to_string_lossy();
+        match name.strip_suffix('!') {
+            Some(name) if is_archive(Path::new(name)) => {
+                archive.push(name);
+                return member_path(components.as_path()).map(|member| (archive, member));
+            },
+            _ => archive.push(component),
+        }
+    }
+    None
+}
+
+/// The entry of a member of the archive entry.
+fn member_entry(archive: &[u8], member: &Path) -> PathBuf {
+    let mut name = OsString::from(paths::from_bytes(archive.to_vec()));
+    name.push("!");
+    PathBuf::from(name).join(member)
+}
+
+/// Lists the members of the zip and tar archives among the entries after each archive, in a background thread.
+pub fn expand_entries(list: PipeReader, location: &Location) -> Result<PipeReader> {
+    let (reader, mut writer) = io::pipe()?;
+    let root = PathBuf::from(&location.path);
+    thread::spawn(move || {
+        let expand = || -> io::Result<()> {
+            for entry in BufReader::new(list).split(b'\n') {
+                let entry = entry?;
+                writer.write_all(&entry)?;
+                writer.write_all(b"\n")?;
+                let path = root.join(paths::from_bytes(entry.clone()));
+                if !is_archive(&path) {
+                    continue;
+                }
+                match members(&path) {
+                    Ok(members) => for member in members {
+                        writer.write_all(&paths::to_bytes(&member_entry(&entry, &member)))?;
+                        writer.write_all(b"\n")?;
+                    },
+                    Err(e) => warn!("Cannot list archive {}: {}", path.to_string_lossy(), e),
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = expand() {
+            // the picker closing the pipe early is expected
+            debug!("Stopped listing archives of {}: {}", root.to_string_lossy(), e);
+        }
+    });
+    Ok(reader)
+}
+
+/// A path relative to the archive, without `.` components, or `None` if it would leave the folder it is extracted to.
+fn member_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => (),
+            _ => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+/// The files inside the archive.
+#[cfg(feature = "archives")]
+pub fn members(archive: &Path) -> Result<Vec<PathBuf>> {
+    let file = std::fs::File::open(archive)?;
+    let name = archive.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        let zip = zip::ZipArchive::new(file)?;
+        return Ok(zip.file_names()
+            .filter(|name| !name.ends_with('/'))
+            .filter_map(|name| member_path(Path::new(name)))
+            .collect());
+    }
+    let reader: Box<dyn io::Read> = match name.ends_with(".tar") {
+        true => Box::new(file),
+        false => Box::new(flate2::read::GzDecoder::new(file)),
+    };
+    let mut members = Vec::new();
+    for entry in tar::Archive::new(reader).entries()? {
+        let entry = entry?;
+        if entry.header().entry_type().is_file() {
+            members.extend(member_path(&entry.path()?));
+        }
+    }
+    Ok(members)
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn members(_archive: &Path) -> Result<Vec<PathBuf>> {
+    Err(anyhow::anyhow!("blink was built without the archives feature"))
+}
+
+/// Extracts a member of an archive, given like `backups/2023.zip!/photos/img001.jpg`, to a temporary folder.
+/// Returns the extracted file, or `None` if the path is no archive member.
+#[cfg(feature = "archives")]
+pub fn extract_member(path: &Path) -> Result<Option<PathBuf>> {
+    let Some((archive, member)) = split_member(path) else {
+        return Ok(None);
+    };
+    let out_path = extract_dir(&archive)?.join(&member);
+    std::fs::create_dir_all(out_path.parent().unwrap_or(Path::new("")))?;
+    debug!("Extracting {} from {}", member.to_string_lossy(), archive.to_string_lossy());
+
+    let file = std::fs::File::open(&archive)?;
+    let name = archive.to_string_lossy().to_lowercase();
+    // an earlier extraction is replaced, without following a link put in its place
+    if out_path.symlink_metadata().is_ok() {
+        std::fs::remove_file(&out_path)?;
+    }
+    let mut out_file = std::fs::OpenOptions::new().write(true).create_new(true).open(&out_path)?;
+    if name.ends_with(".zip") {
+        // zip names always use forward slashes
+        let zip_name = member.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>().join("/");
+        io::copy(&mut zip::ZipArchive::new(file)?.by_name(&zip_name)?, &mut out_file)?;
+        return Ok(Some(out_path));
+    }
+    let reader: Box<dyn io::Read> = match name.ends_with(".tar") {
+        true => Box::new(file),
+        false => Box::new(flate2::read::GzDecoder::new(file)),
+    };
+    for entry in tar::Archive::new(reader).entries()? {
+        let mut entry = entry?;
+        if member_path(&entry.path()?).as_ref() == Some(&member) {
+            io::copy(&mut entry, &mut out_file)?;
+            return Ok(Some(out_path));
+        }
+    }
+    Err(anyhow::anyhow!("{} not found in {}", member.to_string_lossy(), archive.to_string_lossy()))
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn extract_member(path: &Path) -> Result<Option<PathBuf>> {
+    match split_member(path) {
+        Some(_) => Err(anyhow::anyhow!("blink was built without the archives feature")),
+        None => Ok(None),
+    }
+}
+
+/// The folder the members of the archive are extracted to, separate for each archive. They are kept in `archives`
+/// next to the config, which only the user can access, and not in the temporary folder shared with other users.
+#[cfg(feature = "archives")]
+fn extract_dir(archive: &Path) -> Result<PathBuf> {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    let root = crate::config::Config::base_dir().join("archives");
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+        builder.mode(0o700);
+        builder.create(&root)?;
+        std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o700))?;
+    }
+    #[cfg(not(unix))]
+    builder.create(&root)?;
+    let mut hasher = DefaultHasher::new();
+    archive.hash(&mut hasher);
+    let stem = archive.file_name().unwrap_or_default().to_string_lossy();
+    Ok(root.join(format!("{}-{:x}", stem, hasher.finish())))
+}
+
+#[test]
+fn archive_members() {
+    let path = Path::new("backups").join("2023.zip!").join("photos").join("img001.jpg");
+    assert_eq!(split_member(&path), Some((Path::new("backups").join("2023.zip"), Path::new("photos").join("img001.jpg"))));
+    assert_eq!(split_member(Path::new("wow!/a.txt")), None);
+    assert_eq!(split_member(Path::new("a.tar!/../b")), None);
+    assert_eq!(member_entry(b"x.tgz", Path::new("a")), Path::new("x.tgz!").join("a"));
+}
@@ -0,0 +1,159 @@
+use std::{fs::File, io::Cursor, path::Path};
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use zip::read::ZipArchive;
+
+/// Versions downloaded unless another one is requested.
+pub const DEFAULT_FD_VERSION: &str = "9.0.0";
+pub const DEFAULT_FZF_VERSION: &str = "0.46.1";
+
+/// Expected SHA-256 of each release archive, checked against the checksums published with the release.
+/// Archives which are not listed are downloaded with a warning showing their SHA-256, until it is added here.
+const CHECKSUMS: &[(&str, &str)] = &[];
+
+/// A release archive of fd or fzf, and the executable inside it.
+/// Also included by `build.rs`, which bundles them with Windows builds.
+pub struct Asset {
+    pub url: String,
+    pub file_name: String,
+}
+
+/// The fd release for the target triple, e.g. `fd-v9.0.0-aarch64-pc-windows-msvc.zip`.
+pub fn fd_asset(version: &str, target: &str) -> Result<Asset> {
+    let supported = [
+        "x86_64-pc-windows-msvc",
+        "aarch64-pc-windows-msvc",
+        "i686-pc-windows-msvc",
+        "x86_64-unknown-linux-gnu",
+        "x86_64-unknown-linux-musl",
+        "aarch64-unknown-linux-gnu",
+        "aarch64-unknown-linux-musl",
+        "x86_64-apple-darwin",
+        "aarch64-apple-darwin",
+    ];
+    if !supported.contains(&target) {
+        return Err(anyhow::anyhow!("No fd release for target {}", target));
+    }
+    let version = version.trim_start_matches('v');
+    let windows = target.contains("windows");
+    Ok(Asset {
+        url: format!(
+            "https://github.com/sharkdp/fd/releases/download/v{0}/fd-v{0}-{1}.{2}",
+            version,
+            target,
+            if windows { "zip" } else { "tar.gz" }
+        ),
+        file_name: if windows { "fd.exe" } else { "fd" }.to_owned(),
+    })
+}
+
+/// The fzf release for the target triple, e.g. `fzf-0.46.1-windows_arm64.zip`.
+pub fn fzf_asset(version: &str, target: &str) -> Result<Asset> {
+    let arch = match target.split('-').next() {
+        Some("x86_64") => "amd64",
+        Some("aarch64") => "arm64",
+        _ => return Err(anyhow::anyhow!("No fzf release for target {}", target)),
+    };
+    let (os, ext) = if target.contains("windows") {
+        ("windows", "zip")
+    } else if target.contains("linux") {
+        ("linux", "tar.gz")
+    } else if target.contains("darwin") {
+        ("darwin", "zip")
+    } else {
+        return Err(anyhow::anyhow!("No fzf release for target {}", target));
+    };
+    Ok(Asset {
+        url: format!(
+            "https://github.com/junegunn/fzf/releases/download/{0}/fzf-{0}-{1}_{2}.{3}",
+            version.trim_start_matches('v'), os, arch, ext
+        ),
+        file_name: if os == "windows" { "fzf.exe" } else { "fzf" }.to_owned(),
+    })
+}
+
+/// The target triple of the releases for this system. Linux uses the static musl builds of fd.
+pub fn host_target() -> Result<String> {
+    let arch = std::env::consts::ARCH;
+    match std::env::consts::OS {
+        "windows" => Ok(format!("{}-pc-windows-msvc", arch)),
+        "linux" => Ok(format!("{}-unknown-linux-musl", arch)),
+        "macos" => Ok(format!("{}-apple-darwin", arch)),
+        os => Err(anyhow::anyhow!("No fd and fzf releases for {}", os)),
+    }
+}
+
+/// Whether the SHA-256 of the archive is pinned, so [`verify_checksum`] checks it.
+pub fn is_pinned(url: &str) -> bool {
+    CHECKSUMS.iter().any(|(pinned_url, _)| *pinned_url == url)
+}
+
+/// The SHA-256 of the archive, as hex digits.
+pub fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Checks the archive against its pinned SHA-256. Archives which are not pinned pass, see [`is_pinned`].
+pub fn verify_checksum(url: &str, bytes: &[u8]) -> Result<()> {
+    let actual = sha256(bytes);
+    match CHECKSUMS.iter().find(|(pinned_url, _)| *pinned_url == url) {
+        Some((_, expected)) if !expected.eq_ignore_ascii_case(&actual) => Err(anyhow::anyhow!(
+            "SHA-256 of {} does not match\n  expected: {}\n  actual:   {}\nThe download is corrupted or was tampered with",
+            url, expected, actual
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the archive entry is the executable, and not e.g. a folder of the same name.
+fn is_executable_entry(entry: &Path, file_name: &str) -> bool {
+    entry.file_name().is_some_and(|name| name == file_name)
+}
+
+/// Writes the executable of the downloaded zip or tar.gz archive to `out_path`.
+pub fn extract(asset: &Asset, bytes: Vec<u8>, out_path: &Path) -> Result<()> {
+    let file_name = &asset.file_name;
+    if asset.url.ends_with(".zip") {
+        let mut zip = ZipArchive::new(Cursor::new(bytes))?;
+        let zip_file_path: String = zip
+            .file_names()
+            .find(|name| is_executable_entry(Path::new(name), file_name))
+            .ok_or_else(|| anyhow::anyhow!("{} not found in zip", file_name))?
+            .into();
+        let mut file = zip.by_name(&zip_file_path)?;
+        let mut out_file = File::create(out_path)?;
+        std::io::copy(&mut file, &mut out_file)?;
+    } else {
+        let mut archive = tar::Archive::new(GzDecoder::new(Cursor::new(bytes)));
+        let mut file = archive
+            .entries()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.path().is_ok_and(|path| is_executable_entry(&path, file_name)))
+            .ok_or_else(|| anyhow::anyhow!("{} not found in archive", file_name))?;
+        let mut out_file = File::create(out_path)?;
+        std::io::copy(&mut file, &mut out_file)?;
+    }
+    // neither archive format keeps the mode when extracted like this
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(out_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn release_urls() {
+    assert_eq!(fd_asset("9.0.0", "x86_64-pc-windows-msvc").unwrap().url,
+        "https://github.com/sharkdp/fd/releases/download/v9.0.0/fd-v9.0.0-x86_64-pc-windows-msvc.zip");
+    assert_eq!(fzf_asset("0.46.1", "x86_64-pc-windows-msvc").unwrap().url,
+        "https://github.com/junegunn/fzf/releases/download/0.46.1/fzf-0.46.1-windows_amd64.zip");
+    assert_eq!(fzf_asset("v0.46.1", "aarch64-unknown-linux-musl").unwrap().url,
+        "https://github.com/junegunn/fzf/releases/download/0.46.1/fzf-0.46.1-linux_arm64.tar.gz");
+    assert_eq!(fd_asset("9.0.0", "aarch64-unknown-linux-musl").unwrap().file_name, "fd");
+    assert!(fd_asset("9.0.0", "riscv64gc-unknown-linux-gnu").is_err());
+    // unpinned archives pass, the callers warn about them
+    assert!(!is_pinned("https://example.com/fd.tar.gz") && verify_checksum("https://example.com/fd.tar.gz", b"fd").is_ok());
+    assert_eq!(sha256(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+}
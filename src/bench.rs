@@ -0,0 +1,97 @@
+use std::{io::{BufRead, BufReader}, process::Stdio, time::{Duration, Instant}};
+use anyhow::Result;
+use blink_search::config::{Config, Location, LocationMode};
+use blink_search::index::{normalize_entries, read_location_from_cache, Separator};
+use blink_search::{cache, error, filter, finder, script, WithFunction};
+use log::debug;
+use crate::stats::count_lines;
+
+/// The duration and number of entries of one stage.
+type Timing = (Duration, usize);
+
+/// The stages of listing a location, each timed on its own.
+#[derive(Default)]
+struct Run {
+    cache_read: Option<Timing>,
+    scan: Option<Timing>,
+    normalize: Option<Timing>,
+    first_entry: Option<Duration>,
+    all_entries: Option<Timing>,
+}
+
+fn run_once(location: &Location, config: &Config) -> Result<Run> {
+    let mut run = Run::default();
+
+    if let Some(path) = cache::cache_path(location).filter(|p| p.exists()) {
+        let start = Instant::now();
+        let count = count_lines(read_location_from_cache(path)?)?;
+        run.cache_read = Some((start.elapsed(), count));
+    }
+
+    // the finder output is kept in memory, so normalizing it is timed without the scan
+    let uses_finder = location.list_command.is_none() && location.remote.is_none();
+    if uses_finder && matches!(location.mode, LocationMode::Files | LocationMode::Folders | LocationMode::All) {
+        let mut finder = finder::finder_cmd(location.finder, location, config);
+        let start = Instant::now();
+        let output = finder.cmd
+            .stdin(Stdio::null())
+            .stderr(Stdio::inherit())
+            .with(|b| debug!("Executing: {:?}", b))
+            .output()
+            .map_err(|e| error::spawn_error(e, &finder.cmd))?;
+        let separator = match finder.separator {
+            Separator::Null => b'\0',
+            _ => b'\n',
+        };
+        let scanned = output.stdout.split(|&b| b == separator).filter(|e| !e.is_empty()).count();
+        run.scan = Some((start.elapsed(), scanned));
+
+        let start = Instant::now();
+        let mut normalized = Vec::new();
+        normalize_entries(output.stdout.as_slice(), &mut normalized, finder.separator, finder.strip_prefix.as_deref(), finder.only_type)?;
+        run.normalize = Some((start.elapsed(), normalized.split(|&b| b == b'\n').filter(|e| !e.is_empty()).count()));
+    }
+
+    // like the search, but without refreshing a stale cache in the background
+    let start = Instant::now();
+    let (list, _watchdog) = finder::for_location(location).list(location, config)?;
+    let mut list = BufReader::new(script::transform_entries(filter::filter_entries(list, location)?, config)?);
+    let mut first = Vec::new();
+    if list.read_until(b'\n', &mut first)? > 0 {
+        run.first_entry = Some(start.elapsed());
+    }
+    let count = count_lines(list)? + usize::from(!first.is_empty());
+    run.all_entries = Some((start.elapsed(), count));
+    Ok(run)
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    duration.map_or("-".to_owned(), |d| format!("{:.1?}", d))
+}
+
+/// Lists the location twice and prints how long each stage took. The first, cold run includes
+/// reading from disk, the second, warm run mostly hits the file system cache of the OS.
+pub fn run(location_name: &str, location: &Location, config: &Config) -> Result<()> {
+    if !finder::is_available(location) {
+        return Err(anyhow::anyhow!("{} is not available", location.path));
+    }
+    println!("Benchmarking {}...", location_name);
+    let cold = run_once(location, config)?;
+    let warm = run_once(location, config)?;
+
+    println!("{:<24} {:>10} {:>10} {:>10}", "stage", "cold", "warm", "entries");
+    let duration = |timing: Option<Timing>| timing.map(|(d, _)| d);
+    let entries = |cold: Option<Timing>, warm: Option<Timing>| warm.or(cold).map(|(_, n)| n);
+    let stages = [
+        ("cache read", duration(cold.cache_read), duration(warm.cache_read), entries(cold.cache_read, warm.cache_read)),
+        ("finder scan", duration(cold.scan), duration(warm.scan), entries(cold.scan, warm.scan)),
+        ("normalization", duration(cold.normalize), duration(warm.normalize), entries(cold.normalize, warm.normalize)),
+        ("first entry", cold.first_entry, warm.first_entry, None),
+        ("all entries", duration(cold.all_entries), duration(warm.all_entries), entries(cold.all_entries, warm.all_entries)),
+    ];
+    for (stage, cold, warm, entries) in stages {
+        let entries = entries.map_or("-".to_owned(), |n| n.to_string());
+        println!("{:<24} {:>10} {:>10} {:>10}", stage, format_duration(cold), format_duration(warm), entries);
+    }
+    Ok(())
+}
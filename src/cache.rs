@@ -0,0 +1,634 @@
+use std::{collections::HashSet, fs::TryLockError, sync::{Mutex, OnceLock}, thread, time::Instant, fs::File, io::{self, BufRead, BufReader, BufWriter, PipeReader, Read, Write}, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+use anyhow::Result;
+use log::{debug, info, warn};
+use crate::config::{Config, Location};
+use linked_hash_map::LinkedHashMap;
+use crate::finder::{CacheFile, Watchdog};
+use crate::index::read_location_live;
+use crate::crypt::{self, FinishWrite};
+use crate::{finder, location_to_id, paths};
+
+/// Prefix of the entries of `source: merged` locations which were only found in the cache file,
+/// so they may have been deleted since.
+pub const CACHE_ONLY_MARKER: &str = "✗ ";
+
+/// `cache_file: auto` keeps the cache file in the `cache` folder next to the config file,
+/// for locations which cannot be written to. It is named after the location, a hash of its name and its mode,
+/// and kept uncompressed so large ones are memory-mapped, see [`can_map`].
+pub const AUTO: &str = "auto";
+
+/// A short hash of the exact location name, so that names like `Client Share` and `clientshare` get their own
+/// cache files. FNV-1a, which stays the same across builds.
+fn name_hash(name: &str) -> String {
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, b| (hash ^ u32::from(b)).wrapping_mul(0x01000193));
+    format!("{:08x}", hash)
+}
+
+pub fn cache_path(location: &Location) -> Option<PathBuf> {
+    match location.cache_file.as_deref()? {
+        AUTO => {
+            // names without letters or digits are only told apart by the hash
+            let file_name = match location_to_id(&location.name).unwrap_or_default() {
+                id if id.is_empty() => format!("{}-{}.txt", name_hash(&location.name), location.mode),
+                id => format!("{}-{}-{}.txt", id, name_hash(&location.name), location.mode),
+            };
+            Some(Config::base_dir().join("cache").join(file_name))
+        },
+        file => Some(Path::new(&location.path).join(file)),
+    }
+}
+
+/// Cache files ending in `.zst` are stored zstd-compressed.
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "zst")
+}
+
+/// Starts the cache files in the binary format, followed by the version and the flags.
+const BINARY_MAGIC: &[u8] = b"BLINKBIN";
+const BINARY_VERSION: u8 = 1;
+/// Flag of binary cache files whose entries are sorted.
+const BINARY_SORTED: u8 = 1;
+
+/// Cache files ending in `.bin` or `.bin.zst` hold each entry as its length, a little-endian `u32`, and its bytes,
+/// which is read much faster than lines.
+pub fn is_binary(path: &Path) -> bool {
+    let path = if is_compressed(path) { path.file_stem().map(Path::new).unwrap_or(path) } else { path };
+    path.extension().is_some_and(|e| e == "bin")
+}
+
+/// The longest entry of binary cache files, well above the longest paths of any platform.
+/// A longer length means the cache file is damaged.
+const MAX_ENTRY_LEN: usize = 128 * 1024;
+
+fn write_entry(out: &mut impl Write, entry: &[u8], binary: bool) -> io::Result<()> {
+    if binary {
+        if entry.len() > MAX_ENTRY_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "entry too long"));
+        }
+        out.write_all(&(entry.len() as u32).to_le_bytes())?;
+        return out.write_all(entry);
+    }
+    out.write_all(entry)?;
+    out.write_all(b"\n")
+}
+
+/// Uncompressed text cache files of at least this size are memory-mapped instead of read line by line,
+/// if they are in blink's own `cache` folder, see [`can_map`].
+pub const MAP_MIN_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Whether the cache file is in the `cache` folder of `cache_file: auto`, which only blink writes to.
+/// Compressed, binary and encrypted cache files are never mapped.
+/// Cache files in the locations may be changed by others, e.g. on a share, while they are read.
+pub fn can_map(path: &Path) -> bool {
+    path.starts_with(Config::base_dir().join("cache"))
+}
+
+/// Lists the entries of a text cache file by mapping it into memory, in a background thread.
+/// Lines which are already clean are written as they are, without copying them first.
+/// Only for files in blink's own `cache` folder, see [`can_map`].
+pub fn read_mapped(file: File) -> Result<PipeReader> {
+    // SAFETY: blink replaces the files of its `cache` folder by renaming a new file over them and never
+    // changes them in place. A file truncated by another program would crash blink with SIGBUS.
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    let (reader, writer) = io::pipe()?;
+    thread::spawn(move || {
+        let mut out = BufWriter::with_capacity(1 << 16, writer);
+        let mut write = || -> io::Result<()> {
+            for line in map.split(|&b| b == b'\n') {
+                match paths::is_clean_entry(line) {
+                    true => out.write_all(line)?,
+                    false => match paths::clean_entry(line) {
+                        Some(entry) => out.write_all(&paths::to_bytes(&entry))?,
+                        None => continue,
+                    },
+                }
+                out.write_all(b"\n")?;
+            }
+            out.flush()
+        };
+        if let Err(e) = write() {
+            // the picker closing the pipe early is expected
+            debug!("Stopped reading the cache file: {}", e);
+        }
+    });
+    Ok(reader)
+}
+
+/// Reads the next entry of a binary cache file, or returns `false` at its end.
+/// A cut off or overlong entry is an [`io::ErrorKind::InvalidData`] error.
+fn read_binary_entry(input: &mut impl BufRead, entry: &mut Vec<u8>) -> io::Result<bool> {
+    if input.fill_buf()?.is_empty() {
+        return Ok(false);
+    }
+    let damaged = |e: io::Error| match e.kind() {
+        io::ErrorKind::UnexpectedEof => io::Error::new(io::ErrorKind::InvalidData, "the last entry is cut off"),
+        _ => e,
+    };
+    let mut len = [0; 4];
+    input.read_exact(&mut len).map_err(damaged)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_ENTRY_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("an entry of {} bytes", len)));
+    }
+    entry.resize(len, 0);
+    input.read_exact(entry).map_err(damaged)?;
+    Ok(true)
+}
+
+/// Lists the entries of a cache file in the binary format, one per line, in a background thread.
+/// If the entries are damaged, the cache file `damaged_path` is removed, so it is created again
+/// or the next search asks to update it.
+pub fn read_binary(input: impl Read + Send + 'static, damaged_path: Option<&Path>) -> Result<PipeReader> {
+    let mut input = BufReader::new(input);
+    let mut header = [0; BINARY_MAGIC.len() + 2];
+    input.read_exact(&mut header)?;
+    if &header[..BINARY_MAGIC.len()] != BINARY_MAGIC {
+        return Err(anyhow::anyhow!("Not a binary cache file, run `bl --update-cache` to recreate it"));
+    }
+    if header[BINARY_MAGIC.len()] != BINARY_VERSION {
+        return Err(anyhow::anyhow!("Unknown version {} of the binary cache file, run `bl --update-cache` to recreate it", header[BINARY_MAGIC.len()]));
+    }
+    let (reader, mut writer) = io::pipe()?;
+    let damaged_path = damaged_path.map(Path::to_owned);
+    thread::spawn(move || {
+        let mut out = io::BufWriter::new(&mut writer);
+        let mut entry = Vec::new();
+        loop {
+            match read_binary_entry(&mut input, &mut entry) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => {
+                    warn!("Cannot read the binary cache file: {}", e);
+                    if let Some(path) = damaged_path.as_ref().filter(|_| e.kind() == io::ErrorKind::InvalidData) {
+                        warn!("Removing damaged cache file \"{}\"", path.to_string_lossy());
+                        if let Err(e) = std::fs::remove_file(path) {
+                            warn!("Cannot remove cache file {}: {}", path.to_string_lossy(), e);
+                        }
+                    }
+                    break;
+                },
+            }
+            if let Err(e) = out.write_all(&entry).and_then(|_| out.write_all(b"\n")) {
+                // the picker closing the pipe early is expected
+                debug!("Stopped reading the binary cache file: {}", e);
+                return;
+            }
+        }
+        let _ = out.flush();
+    });
+    Ok(reader)
+}
+
+pub fn cache_age(path: &Path) -> Option<Duration> {
+    let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+/// A coarse age like `3 days` or `5 minutes`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    let (count, unit) = match secs {
+        s if s < 60 => return "less than a minute".to_owned(),
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 24 * 60 * 60 => (s / (60 * 60), "hour"),
+        s => (s / (24 * 60 * 60), "day"),
+    };
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// A cache is stale if it is older than the location's `cache_ttl`. Missing caches are never stale.
+pub fn is_stale(location: &Location) -> bool {
+    match (cache_path(location), location.cache_ttl) {
+        (Some(path), Some(ttl)) => cache_age(&path).is_some_and(|age| age > ttl),
+        _ => false,
+    }
+}
+
+/// An exclusive lock on a cache file, held while it is written, so a scheduled and an interactive update cannot mix
+/// their entries. It is kept in a `.lock` file next to the cache file and released when dropped.
+pub struct CacheLock {
+    _file: File,
+}
+
+impl CacheLock {
+    fn open(path: &Path) -> Result<File> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let lock_path = path.with_file_name(format!("{}.lock", path.file_name().unwrap_or_default().to_string_lossy()));
+        Ok(File::options().create(true).truncate(false).write(true).open(lock_path)?)
+    }
+
+    /// Locks the cache file, waiting for another update of it to finish first.
+    pub fn acquire(path: &Path) -> Result<CacheLock> {
+        let file = Self::open(path)?;
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                info!("Waiting for another update of \"{}\"", path.to_string_lossy());
+                file.lock()?;
+            },
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        Ok(CacheLock { _file: file })
+    }
+
+    /// Locks the cache file, or returns `None` if it is being updated.
+    pub fn try_acquire(path: &Path) -> Result<Option<CacheLock>> {
+        let file = Self::open(path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(CacheLock { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+/// Whether the cache file of the location is being written by another update right now.
+pub fn is_updating(location: &Location) -> bool {
+    cache_path(location).is_some_and(|path| matches!(CacheLock::try_acquire(&path), Ok(None)))
+}
+
+/// Writes a new cache file next to the old one, which it replaces once finished.
+pub struct CacheWriter {
+    _lock: CacheLock,
+    path: PathBuf,
+    tmp_path: PathBuf,
+    out: CacheOut,
+    binary: bool,
+    /// The entries to write once finished, when they are sorted
+    sorted: Option<Vec<Vec<u8>>>,
+}
+
+enum CacheOut {
+    Plain(BufWriter<Box<dyn FinishWrite>>),
+    Compressed(zstd::Encoder<'static, Box<dyn FinishWrite>>),
+}
+
+impl CacheWriter {
+    /// Writes the cache file of the location at `path`, sorted and encrypted as configured, holding its lock until finished.
+    pub fn create(path: &Path, location: &Location, lock: CacheLock) -> Result<CacheWriter> {
+        let tmp_path = path.with_extension("tmp");
+        let file = crypt::create(&tmp_path, location.encrypt_cache)?;
+        let sort = location.sort_cache;
+        let mut out = match is_compressed(path) {
+            true => CacheOut::Compressed(zstd::Encoder::new(file, 0)?),
+            false => CacheOut::Plain(BufWriter::new(file)),
+        };
+        let binary = is_binary(path);
+        if binary {
+            out.write_all(BINARY_MAGIC)?;
+            out.write_all(&[BINARY_VERSION, if sort { BINARY_SORTED } else { 0 }])?;
+        }
+        Ok(CacheWriter { _lock: lock, path: path.to_owned(), tmp_path, out, binary, sorted: sort.then(Vec::new) })
+    }
+
+    pub fn write_entry(&mut self, entry: &[u8]) -> io::Result<()> {
+        match &mut self.sorted {
+            Some(entries) => {
+                entries.push(entry.to_vec());
+                Ok(())
+            },
+            None => write_entry(&mut self.out, entry, self.binary),
+        }
+    }
+
+    /// Replaces the cache file with the written entries.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(mut entries) = self.sorted.take() {
+            entries.sort();
+            for entry in entries {
+                write_entry(&mut self.out, &entry, self.binary)?;
+            }
+        }
+        match self.out {
+            CacheOut::Plain(out) => out.into_inner().map_err(|e| e.into_error())?.finish()?,
+            CacheOut::Compressed(out) => out.finish()?.finish()?,
+        }
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        // the rewritten cache file holds the changes of all deltas
+        for delta in delta_paths(&self.path) {
+            std::fs::remove_file(delta)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the old cache file.
+    pub fn discard(self) {
+        drop(self.out);
+        if let Err(e) = std::fs::remove_file(&self.tmp_path) {
+            debug!("Cannot remove {}: {}", self.tmp_path.to_string_lossy(), e);
+        }
+    }
+}
+
+impl Write for CacheOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CacheOut::Plain(out) => out.write(buf),
+            CacheOut::Compressed(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CacheOut::Plain(out) => out.flush(),
+            CacheOut::Compressed(out) => out.flush(),
+        }
+    }
+}
+
+/// Scans the location and replaces its cache file. Returns the number of entries written.
+pub fn update(location: &Location, config: &Config) -> Result<usize> {
+    let path = cache_path(location)
+        .ok_or_else(|| anyhow::anyhow!("Location {} has no cache_file", location.path))?;
+    if location.path.is_empty() {
+        return Err(anyhow::anyhow!("Location has no path to scan"));
+    }
+    if !finder::is_available(location) {
+        return Err(anyhow::anyhow!("{} is not available", location.path));
+    }
+    let lock = CacheLock::acquire(&path)?;
+    info!("Updating cache file: \"{}\"", path.to_string_lossy());
+
+    if path.exists() && location.cache_deltas.is_some_and(|max| delta_paths(&path).len() < max) {
+        return update_delta(&path, location, config);
+    }
+    let mut cache = CacheWriter::create(&path, location, lock)?;
+    let (list, watchdog) = read_location_live(location, config)?;
+    let mut count = 0;
+    for entry in BufReader::new(list).split(b'\n') {
+        cache.write_entry(&entry?)?;
+        count += 1;
+    }
+    if watchdog.timed_out() || watchdog.failed() {
+        cache.discard();
+        return Err(scan_error(location, &watchdog));
+    }
+    cache.finish()?;
+
+    info!("Cache file \"{}\" written with {} entries", path.to_string_lossy(), count);
+    Ok(count)
+}
+
+/// Scans the location and writes the entries added and removed since the cache file and its deltas to a new delta file.
+/// Returns the number of entries found. The cache file must be locked.
+fn update_delta(path: &Path, location: &Location, config: &Config) -> Result<usize> {
+    let mut removed: HashSet<Vec<u8>> = BufReader::new(CacheFile(path.to_owned()).read()?).split(b'\n').collect::<io::Result<_>>()?;
+    let mut added = Vec::new();
+    let mut count = 0;
+    let (list, watchdog) = read_location_live(location, config)?;
+    for entry in BufReader::new(list).split(b'\n') {
+        let entry = entry?;
+        if !removed.remove(&entry) {
+            added.push(entry);
+        }
+        count += 1;
+    }
+    if watchdog.timed_out() || watchdog.failed() {
+        return Err(scan_error(location, &watchdog));
+    }
+
+    if !added.is_empty() || !removed.is_empty() {
+        let stamp = humantime::format_rfc3339_millis(SystemTime::now()).to_string().replace(':', "");
+        let delta_path = path.with_file_name(format!("{}.{}.delta", path.file_name().unwrap_or_default().to_string_lossy(), stamp));
+        let tmp_path = delta_path.with_extension("tmp");
+        let mut file = BufWriter::new(crypt::create(&tmp_path, location.encrypt_cache)?);
+        for (sign, entry) in added.iter().map(|e| (b'+', e)).chain(removed.iter().map(|e| (b'-', e))) {
+            file.write_all(&[sign])?;
+            file.write_all(entry)?;
+            file.write_all(b"\n")?;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.finish()?;
+        std::fs::rename(&tmp_path, &delta_path)?;
+        info!("Delta file \"{}\" written with {} added and {} removed entries", delta_path.to_string_lossy(), added.len(), removed.len());
+    }
+    // the age of the cache is that of its last update
+    File::options().write(true).open(path)?.set_modified(SystemTime::now())?;
+    Ok(count)
+}
+
+/// Why a scan cut off by the watchdog does not replace the cache.
+fn scan_error(location: &Location, watchdog: &Watchdog) -> anyhow::Error {
+    let reason = if watchdog.timed_out() { "timed out" } else { "failed" };
+    anyhow::anyhow!("Scanning {} {}, keeping the old cache file", location.path, reason)
+}
+
+/// The delta files of the cache file, like `cache.txt.zst.2024-10-16T030000.000Z.delta`, oldest first.
+pub fn delta_paths(path: &Path) -> Vec<PathBuf> {
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut deltas: Vec<PathBuf> = std::fs::read_dir(dir).into_iter().flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_str().is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".delta")))
+        .map(|e| e.path())
+        .collect();
+    deltas.sort();
+    deltas
+}
+
+/// Lists the entries of a cache file with the changes of its delta files applied, in a background thread.
+/// Lines of a delta start with `+` for added and `-` for removed entries, the last change of an entry wins.
+pub fn apply_deltas(base: impl Read + Send + 'static, deltas: &[PathBuf]) -> Result<PipeReader> {
+    let mut changes: LinkedHashMap<Vec<u8>, bool> = LinkedHashMap::new();
+    for delta in deltas {
+        let file = match crypt::open(delta) {
+            Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound) => {
+                // the cache file was rewritten with its changes meanwhile
+                debug!("Delta file {} was removed", delta.to_string_lossy());
+                continue;
+            },
+            file => file?,
+        };
+        for line in BufReader::new(file).split(b'\n') {
+            match line?.split_first() {
+                Some((b'+', entry)) => changes.insert(entry.to_vec(), true),
+                Some((b'-', entry)) => changes.insert(entry.to_vec(), false),
+                _ => None,
+            };
+        }
+    }
+    let (reader, mut writer) = io::pipe()?;
+    thread::spawn(move || {
+        let write = || -> io::Result<()> {
+            for entry in BufReader::new(base).split(b'\n') {
+                let entry = entry?;
+                if !changes.contains_key(&entry) {
+                    writer.write_all(&entry)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            for (entry, _) in changes.iter().filter(|(_, added)| **added) {
+                writer.write_all(entry)?;
+                writer.write_all(b"\n")?;
+            }
+            Ok(())
+        };
+        if let Err(e) = write() {
+            // the picker closing the pipe early is expected
+            debug!("Stopped reading the cache file: {}", e);
+        }
+    });
+    Ok(reader)
+}
+
+/// How stale caches are refreshed, if not in a thread. See [`set_background_refresh`].
+static BACKGROUND_REFRESH: OnceLock<fn(&str) -> Result<()>> = OnceLock::new();
+
+/// Sets how the cache of a location is refreshed in the background, e.g. in a process which outlives the caller.
+/// Without it, the cache is updated in a thread of this process.
+pub fn set_background_refresh(refresh: fn(&str) -> Result<()>) {
+    let _ = BACKGROUND_REFRESH.set(refresh);
+}
+
+/// Updates the cache of the location in the background, so the stale cache can be used meanwhile.
+pub fn refresh_in_background(location_name: &str, location: &Location, config: &Config) -> Result<()> {
+    info!("Cache of {} is stale, refreshing in background", location_name);
+    if let Some(refresh) = BACKGROUND_REFRESH.get() {
+        return refresh(location_name);
+    }
+    let (name, location, config) = (location_name.to_owned(), location.clone(), config.clone());
+    thread::spawn(move || {
+        if let Err(e) = update(&location, &config) {
+            warn!("Cannot refresh the cache of {}: {}", name, e);
+        }
+    });
+    Ok(())
+}
+
+/// Updates the caches of all locations with a `cache_file`, running up to `jobs` scans at once.
+pub fn update_all(config: &Config, jobs: usize) -> Result<()> {
+    let queue = Mutex::new(config.enabled_locations().filter(|(_, loc)| loc.cache_file.is_some()));
+    let skipped = Mutex::new(Vec::new());
+    let results = Mutex::new(Vec::new());
+
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1) {
+            s.spawn(|| loop {
+                let Some((name, loc)) = queue.lock().unwrap().next() else { break };
+                if loc.optional && !finder::is_available(loc) {
+                    println!("[{}] skipped, {} is not available", name, loc.path);
+                    skipped.lock().unwrap().push(name);
+                    continue;
+                }
+                println!("[{}] updating...", name);
+                let start = Instant::now();
+                let result = update(loc, config);
+                let elapsed = start.elapsed();
+                match &result {
+                    Ok(count) => println!("[{}] {} entries in {:.1?}", name, count, elapsed),
+                    Err(e) => println!("[{}] failed after {:.1?}: {}", name, elapsed, e),
+                }
+                results.lock().unwrap().push((name, result, elapsed));
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap();
+    let skipped = skipped.into_inner().unwrap();
+    let failed = results.iter().filter(|(_, r, _)| r.is_err()).count();
+    println!();
+    println!("Summary:");
+    for (name, loc) in config.locations.iter().filter(|(_, loc)| loc.cache_file.is_some()) {
+        if skipped.contains(&name) {
+            println!("  {:<20} {:>10} {:>18}  {}", name, "SKIPPED", "", loc.path);
+            continue;
+        }
+        let Some((_, result, elapsed)) = results.iter().find(|(n, _, _)| *n == name) else { continue };
+        match result {
+            Ok(count) => println!("  {:<20} {:>10} entries {:>10.1?}  {}", name, count, elapsed, loc.path),
+            Err(_) => println!("  {:<20} {:>10} {:>18.1?}  {}", name, "FAILED", elapsed, loc.path),
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        n => Err(anyhow::anyhow!("{} of {} cache updates failed", n, results.len())),
+    }
+}
+
+#[test]
+fn coarse_ages() {
+    assert_eq!(format_age(Duration::from_secs(5)), "less than a minute");
+    assert_eq!(format_age(Duration::from_secs(90)), "1 minute");
+    assert_eq!(format_age(Duration::from_secs(5 * 3600)), "5 hours");
+    assert_eq!(format_age(Duration::from_secs(3 * 86400 + 100)), "3 days");
+}
+
+#[test]
+fn deltas_change_the_cache() {
+    let dir = std::env::temp_dir().join(format!("blink-deltas-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let deltas = [dir.join("cache.txt.1.delta"), dir.join("cache.txt.2.delta")];
+    std::fs::write(&deltas[0], "+c\n-a\n+d\n").unwrap();
+    std::fs::write(&deltas[1], "-c\n+a\n").unwrap();
+    assert_eq!(delta_paths(&dir.join("cache.txt")), deltas);
+    let mut entries = String::new();
+    apply_deltas(io::Cursor::new(b"a\nb\n"), &deltas).unwrap().read_to_string(&mut entries).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(entries, "b\nd\na\n");
+}
+
+#[test]
+fn binary_entries() {
+    let mut file = BINARY_MAGIC.to_vec();
+    file.extend([BINARY_VERSION, 0]);
+    for entry in [b"a/b.txt".as_slice(), b"", "caf\u{e9}".as_bytes()] {
+        write_entry(&mut file, entry, true).unwrap();
+    }
+    let mut entries = String::new();
+    read_binary(io::Cursor::new(file.clone()), None).unwrap().read_to_string(&mut entries).unwrap();
+    assert_eq!(entries, "a/b.txt\n\ncaf\u{e9}\n");
+    // a damaged length ends the list instead of allocating gigabytes
+    file.extend(u32::MAX.to_le_bytes());
+    let mut entries = String::new();
+    read_binary(io::Cursor::new(file), None).unwrap().read_to_string(&mut entries).unwrap();
+    assert_eq!(entries, "a/b.txt\n\ncaf\u{e9}\n");
+    assert!(write_entry(&mut Vec::new(), &vec![b'a'; MAX_ENTRY_LEN + 1], true).is_err());
+    assert!(is_binary(Path::new(".blink/cache.bin.zst")) && !is_binary(Path::new("cache.txt")));
+}
+
+#[test]
+fn auto_cache_files() {
+    use crate::config::LocationMode;
+    let config = crate::config::ConfigFormat::Yaml.parse("
+locations:
+  Client Share: { path: /mnt/share, mode: files, cache_file: auto }
+  clientshare: { path: /mnt/other, mode: files, cache_file: auto }
+  ★: { path: /mnt/starred, mode: files, cache_file: auto }
+  docs: { path: /docs, mode: files, cache_file: .blink/cache.txt }
+").unwrap();
+    let share = &config.locations["Client Share"];
+    let cache = Config::base_dir().join("cache");
+    assert_eq!(cache_path(share), Some(cache.join(format!("clientshare-{}-files.txt", name_hash("Client Share")))));
+    assert_eq!(cache_path(&share.in_mode(LocationMode::Folders)), Some(cache.join(format!("clientshare-{}-folders.txt", name_hash("Client Share")))));
+    assert_ne!(cache_path(share), cache_path(&config.locations["clientshare"]));
+    assert_eq!(cache_path(&config.locations["★"]), Some(cache.join(format!("{}-files.txt", name_hash("★")))));
+    let docs = &config.locations["docs"];
+    assert_eq!(cache_path(docs), Some(Path::new("/docs").join(".blink/cache.txt")));
+    assert_eq!(cache_path(&docs.in_mode(LocationMode::Folders)), Some(cache.join(format!("docs-{}-folders.txt", name_hash("docs")))));
+    // large ones are memory-mapped
+    let path = cache_path(share).unwrap();
+    assert!(can_map(&path) && !is_compressed(&path) && !is_binary(&path));
+    assert!(!can_map(&cache_path(docs).unwrap()));
+}
+
+#[test]
+fn mapped_entries() {
+    let path = std::env::temp_dir().join(format!("blink-mapped-{}.txt", std::process::id()));
+    std::fs::write(&path, "./a.txt\r\nsub/b.pdf\n\nc.md").unwrap();
+    let mut entries = String::new();
+    read_mapped(File::open(&path).unwrap()).unwrap().read_to_string(&mut entries).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(entries, "a.txt\nsub/b.pdf\nc.md\n");
+}
+
+#[test]
+fn locked_caches() {
+    let path = std::env::temp_dir().join(format!("blink-lock-{}", std::process::id())).join("cache.txt");
+    let lock = CacheLock::try_acquire(&path).unwrap();
+    assert!(lock.is_some());
+    assert!(CacheLock::try_acquire(&path).unwrap().is_none());
+    drop(lock);
+    assert!(CacheLock::try_acquire(&path).unwrap().is_some());
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
@@ -1,43 +1,621 @@
-use std::path::PathBuf;
+use std::{path::{Path, PathBuf}, time::Duration};
 use linked_hash_map::LinkedHashMap;
-use serde_yaml;
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
+use clap::ValueEnum;
 use directories::BaseDirs;
+use log::debug;
+use crate::error::BlinkError;
+use crate::index::Separator;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub locations: LinkedHashMap<String, Location>,
     pub fd_flags: Option<Vec<String>>,
     pub fzf_flags: Option<Vec<String>>,
+    /// Path of fd, if it is neither `fd` nor Debian's `fdfind` in `$PATH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fd_binary: Option<String>,
+    /// Path of fzf, if it is not `fzf` in `$PATH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fzf_binary: Option<String>,
+    /// Maps glob patterns or extensions to commands used instead of the system default app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_rules: Option<LinkedHashMap<String, String>>,
+    /// Maps fzf key names (e.g. `ctrl-x`) to actions. Merged with the default bindings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keybindings: Option<LinkedHashMap<String, Action>>,
+    /// Actions offered by the `actions` key binding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_menu: Option<Vec<Action>>,
+    /// Editor command used by the `editor` action. Defaults to `$VISUAL` or `$EDITOR`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Whether the editor runs inside the terminal. Guessed from the editor name if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor_terminal: Option<bool>,
+    /// Number of entries kept in the `recent` location. 0 disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recent_size: Option<usize>,
+    /// Number of the most searched other locations loaded in the background while a search or the location menu is shown. 0 disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefetch: Option<usize>,
+    /// The program showing locations and entries.
+    #[serde(default)]
+    pub picker: Picker,
+    /// Global key combination for `bl --hotkey`, like `ctrl+alt+b`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+    /// Port of the HTTP API started by `bl --serve`, on 127.0.0.1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_port: Option<u16>,
+    /// Origin allowed to read HTTP API responses from a browser, like `http://localhost:8080`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_allow_origin: Option<String>,
+    /// How often `bl daemon` rescans the `watch` locations, e.g. `10m`, the default.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub watch_interval: Option<Duration>,
+    /// rhai script with hooks like `on_open(path)`, relative to the config folder. Needs the `scripting` feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    /// Further config files whose locations are added, relative to this one. Missing files are skipped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<String>>,
+    /// The location opened by `bl` without a location name, or `last` for the one used last.
+    /// Defaults to the first enabled location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_location: Option<String>,
+    /// Named lists of globs or extensions, like `images: ["*.png", "*.jpg"]`, used by `--filter` and the `filter` key binding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filters: Option<LinkedHashMap<String, Vec<String>>>,
+    /// How the queries of the picker are remembered. Locations can override it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistorySettings>,
+    /// Level and rotation of `blink.log`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogSettings>,
+    /// Most entries shown in the search. Locations can override it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+    /// How fzf looks. `fzf_flags` can override these settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ui: Option<UiSettings>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Location {
+    /// The folder to search. For `remote` locations, where the remote folder is mounted, if it is.
+    #[serde(default)]
     pub path: String,
     pub mode: LocationMode,
-    pub cache_file: Option<String>
+    /// The list of entries written by `--update-cache`, relative to the path, or `auto` to keep it next to the config file.
+    pub cache_file: Option<String>,
+    /// Maximum age of the cache file, e.g. `12h` or `7d`. Older caches are refreshed in the background.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<Duration>,
+    /// Write the entries of the first scan to the `cache_file` while they are shown, if it does not exist yet.
+    #[serde(default)]
+    pub auto_cache: bool,
+    /// Sort the entries of the cache file by path.
+    #[serde(default)]
+    pub sort_cache: bool,
+    /// Encrypt the cache file and its deltas, with a key kept in the keyring of the system.
+    #[serde(default)]
+    pub encrypt_cache: bool,
+    /// Write the changes found by `--update-cache` to small delta files next to the cache file, up to this many,
+    /// before the cache file is rewritten with all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_deltas: Option<usize>,
+    /// Show the cache immediately, then merge in the results of a concurrent live scan.
+    #[serde(default)]
+    pub revalidate: bool,
+    /// Keep the entries in memory in `bl daemon`, and rescan them and update the cache file every `watch_interval`.
+    #[serde(default)]
+    pub watch: bool,
+    /// For `mode: command`, prints the entries separated by newlines or NUL bytes, e.g. `ssh nas find /share -print0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Prints the entries instead of the `finder`, relative to the path, e.g. `rclone lsf -R remote:bucket`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_command: Option<String>,
+    /// How the entries of `command` or `list_command` are separated: `newline`, `null`, or `any` of both, the default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub separator: Option<Separator>,
+    /// Opens an entry instead of the default application, e.g. `aws s3 cp {path} .`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_command: Option<String>,
+    /// Kill the finder after this long, e.g. `30s`, and use what was found so far.
+    /// An existing cache file is kept if `--update-cache` times out.
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    pub scan_timeout: Option<Duration>,
+    /// Only list entries up to this many levels below the path, like `fd --max-depth`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// Only list entries matching one of these globs or extensions, like `[pdf, "*.md"]`. Set by `--ext` and `--glob`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Vec<String>>,
+    /// Only list entries modified within this time, like `7d`, or since a date like `2024-01-01`. Set by `--newer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newer: Option<String>,
+    /// Only list entries modified before this time, like `30d` ago or `2023-01-01`. Set by `--older`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub older: Option<String>,
+    /// List hidden entries, like `fd --hidden`.
+    #[serde(default)]
+    pub hidden: bool,
+    /// List entries excluded by `.gitignore` and similar files, like `fd --no-ignore`.
+    #[serde(default)]
+    pub no_ignore: bool,
+    /// The path may be missing, e.g. on a removable drive. Unavailable optional locations are skipped
+    /// by `--update-cache all`, and opening one shows the location menu instead of failing.
+    #[serde(default)]
+    pub optional: bool,
+    /// Allow destructive actions like `trash` and `rename` on entries of this location.
+    #[serde(default)]
+    pub allow_delete: bool,
+    /// Name of a group, like `media`. The menu offers each group to search all of its locations together.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Set to `false` to keep the location in the config, but out of the menu and `--update-cache all`.
+    /// It can still be opened by its full name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Further names which open the location exactly, like `dl` for `downloads`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    /// Overrides the `history` settings of the config for searches in this location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistorySettings>,
+    /// Most entries shown in the search, overriding the `max_results` of the config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_results: Option<usize>,
+    #[serde(default)]
+    pub finder: FinderKind,
+    /// Where the entries come from when the location has a `cache_file`.
+    #[serde(default)]
+    pub source: Source,
+    /// In `mode: files`, also list the files inside zip and tar archives, like `backups/2023.zip!/photos/img001.jpg`.
+    /// Needs blink built with the `archives` feature.
+    #[serde(default)]
+    pub index_archives: bool,
+    /// Lists the entries over SSH instead, like `user@host:/srv/data`. Entries open at `path` if it is set,
+    /// else as `sftp://` URLs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    /// A list of entries on the remote, relative to its folder, which is read instead of scanning it,
+    /// like `.blink-cache.txt.zst`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_cache: Option<String>,
+    /// The name of the location in the config, set when it is parsed.
+    #[serde(skip)]
+    pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, ValueEnum, strum::Display)]
 #[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
 pub enum LocationMode {
+    #[default]
     Files,
-    Folders
+    Folders,
+    /// Files and folders, which end with a `/`
+    All,
+    /// Entries are printed by the `command` of the location
+    #[value(skip)]
+    Command,
+    /// Entries of all locations with the same `group`, each starting with the name of its location
+    #[value(skip)]
+    Group,
+    /// Entries are read from stdin, for `bl --stdin`
+    #[value(skip)]
+    Stdin,
+}
+
+/// Where the entries of a location with a cache file come from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The cache file only
+    #[default]
+    Cache,
+    /// A live scan, followed by the entries only found in the cache file, which are marked
+    Merged,
+}
+
+/// The tool used to list the entries of a location when no cache file is used.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FinderKind {
+    #[default]
+    Fd,
+    /// voidtools Everything via `es.exe` (Windows only)
+    Everything,
+    /// Spotlight via `mdfind` (macOS only)
+    Mdfind,
+    /// The system `plocate` database (Linux only)
+    Locate,
+    /// `git ls-files` for locations inside a git repository
+    Git,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PickerKind {
+    #[default]
+    Fzf,
+    /// `rofi -dmenu`, for use without a terminal
+    Rofi,
+    /// `wofi --dmenu` on Wayland
+    Wofi,
+    /// `dmenu` or any other program implementing its protocol
+    Dmenu,
 }
 
-impl Default for LocationMode {
+/// One of the built-in pickers, or a custom fzf-like program.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Picker {
+    Builtin(PickerKind),
+    Custom(PickerCommand),
+}
+
+impl Default for Picker {
     fn default() -> Self {
-        LocationMode::Files
+        Picker::Builtin(PickerKind::Fzf)
     }
 }
 
-impl Default for Location {
-    fn default() -> Self {
-        Location {
-            path: String::new(),
-            mode: LocationMode::default(),
-            cache_file: None
+impl Picker {
+    /// How to talk to the picker. Custom pickers are used like fzf.
+    pub fn kind(&self) -> PickerKind {
+        match self {
+            Picker::Builtin(kind) => *kind,
+            Picker::Custom(_) => PickerKind::Fzf,
+        }
+    }
+
+    /// Whether options only fzf understands, like `--scheme` or `--header`, can be used.
+    pub fn is_fzf(&self) -> bool {
+        matches!(self, Picker::Builtin(PickerKind::Fzf))
+    }
+
+    /// The command of fzf-like pickers, i.e. fzf itself unless a custom one is configured.
+    pub fn command(&self) -> PickerCommand {
+        match self {
+            Picker::Custom(command) => command.clone(),
+            Picker::Builtin(_) => PickerCommand {
+                binary: "fzf".to_owned(),
+                args: Vec::new(),
+                history_arg: Some("--history={path}".to_owned()),
+                bind_arg: Some("--bind={key}:{action}".to_owned()),
+                query_arg: Some("--query={query}".to_owned()),
+            },
+        }
+    }
+}
+
+/// An fzf-compatible program like `sk` or `fzy`, reading entries from stdin and printing the selection.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PickerCommand {
+    pub binary: String,
+    /// Arguments passed on every start.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Argument selecting the history file, like `--history={path}`. No history is kept if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_arg: Option<String>,
+    /// Argument binding a key, like `--bind={key}:{action}`. Key bindings are disabled if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_arg: Option<String>,
+    /// Argument setting the initial query, like `--query={query}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query_arg: Option<String>,
+}
+
+impl PickerCommand {
+    pub fn history_arg(&self, path: &Path) -> Option<String> {
+        self.history_arg.as_ref().map(|a| a.replace("{path}", &path.to_string_lossy()))
+    }
+
+    pub fn bind_arg(&self, key: &str, action: &str) -> Option<String> {
+        self.bind_arg.as_ref().map(|a| a.replace("{key}", key).replace("{action}", action))
+    }
+
+    pub fn query_arg(&self, query: &str) -> Option<String> {
+        self.query_arg.as_ref().map(|a| a.replace("{query}", query))
+    }
+}
+
+/// How the picker remembers queries in the `history-*.txt` files.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct HistorySettings {
+    /// Set to `false` to not remember any queries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Number of queries kept per history file. The file is trimmed when the picker starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+}
+
+/// The appearance of fzf, converted into its flags.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct UiSettings {
+    /// Lines like `20`, or percent of the terminal like `40%`, taken by fzf. Full screen if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<Height>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<Border>,
+    /// Colors of the parts of fzf, like `hl: yellow`, `fg+: "#ebdbb2"` or `prompt: "110:bold"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<LinkedHashMap<ColorName, Color>>,
+    /// Shown before the query, like `{location} ❯ `. Defaults to the name of the location and `> `.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    /// Set to `false` to hide the key bindings shown above the search.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_hints: Option<bool>,
+}
+
+impl UiSettings {
+    /// The fzf flags for the settings, with `{location}` in the prompt replaced by the searched location.
+    pub fn fzf_args(&self, location: Option<&str>) -> Vec<String> {
+        let mut args = Vec::new();
+        args.extend(self.height.as_ref().map(|height| format!("--height={}", height.0)));
+        args.extend(self.layout.map(|layout| format!("--layout={}", layout)));
+        args.extend(self.border.map(|border| format!("--border={}", border)));
+        if let Some(colors) = self.colors.as_ref().filter(|colors| !colors.is_empty()) {
+            let colors: Vec<String> = colors.iter().map(|(name, color)| format!("{}:{}", name, color.0)).collect();
+            args.push(format!("--color={}", colors.join(",")));
+        }
+        args.extend(self.prompt.as_ref().map(|prompt| format!("--prompt={}", prompt.replace("{location}", location.unwrap_or_default()))));
+        args
+    }
+}
+
+/// A number or text in the config, for values like `20` or `40%`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scalar {
+    Number(i64),
+    Text(String),
+}
+
+impl From<Scalar> for String {
+    fn from(scalar: Scalar) -> String {
+        match scalar {
+            Scalar::Number(n) => n.to_string(),
+            Scalar::Text(text) => text,
+        }
+    }
+}
+
+/// The height of fzf, like `20` lines or `40%`. `~` in front shrinks fzf to fit fewer entries.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "Scalar", into = "String")]
+pub struct Height(String);
+
+impl TryFrom<Scalar> for Height {
+    type Error = String;
+
+    fn try_from(value: Scalar) -> Result<Self, Self::Error> {
+        let value = String::from(value);
+        let number = value.trim_start_matches('~');
+        let valid = match number.strip_suffix('%') {
+            Some(percent) => percent.parse::<u8>().is_ok_and(|p| p <= 100),
+            None => number.parse::<u16>().is_ok(),
+        };
+        match valid {
+            true => Ok(Height(value)),
+            false => Err(format!("invalid height {}, expected lines like 20 or percent like 40%", value)),
+        }
+    }
+}
+
+impl From<Height> for String {
+    fn from(height: Height) -> String {
+        height.0
+    }
+}
+
+/// Where the query is shown.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum Layout {
+    /// The query at the bottom
+    Default,
+    /// The query at the top, the list below
+    Reverse,
+    /// The query at the bottom, the list from the top
+    ReverseList,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Border {
+    Rounded,
+    Sharp,
+    Bold,
+    Double,
+    Block,
+    Horizontal,
+    Vertical,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    None,
+}
+
+/// The parts of fzf which can be colored.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ColorName {
+    Fg,
+    Bg,
+    Hl,
+    #[serde(rename = "fg+")]
+    #[strum(serialize = "fg+")]
+    FgSelected,
+    #[serde(rename = "bg+")]
+    #[strum(serialize = "bg+")]
+    BgSelected,
+    #[serde(rename = "hl+")]
+    #[strum(serialize = "hl+")]
+    HlSelected,
+    PreviewFg,
+    PreviewBg,
+    Gutter,
+    Query,
+    Info,
+    Border,
+    Separator,
+    Scrollbar,
+    Prompt,
+    Pointer,
+    Marker,
+    Spinner,
+    Header,
+}
+
+/// A color like `yellow`, `214`, `#ebdbb2` or `-1` for the terminal default, optionally followed by
+/// attributes like `:bold`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "Scalar", into = "String")]
+pub struct Color(String);
+
+impl TryFrom<Scalar> for Color {
+    type Error = String;
+
+    fn try_from(value: Scalar) -> Result<Self, Self::Error> {
+        let value = String::from(value);
+        let valid = value.split(':').all(|part| {
+            part == "-1"
+                || part.parse::<u8>().is_ok()
+                || part.strip_prefix('#').is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                || (!part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c == '-'))
+        });
+        match valid {
+            true => Ok(Color(value)),
+            false => Err(format!("invalid color {}, expected a name like yellow, 0 to 255, or #rrggbb", value)),
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        color.0
+    }
+}
+
+/// How much is written to `blink.log`, and when it is rotated.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct LogSettings {
+    /// Defaults to `debug`, which includes every opened path. `--log-level` overrides it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<LogLevel>,
+    /// Size in MB after which the log is renamed to `blink.log.1`. Defaults to 5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+    /// Number of log files kept, including the current one. Defaults to 3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// What happens when a bound key is pressed in the search.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Action {
+    Builtin(BuiltinAction),
+    /// Run a command like `code {path}` on the selection.
+    Command { command: String },
+    /// Only show the entries matching one of the `filters`, or all entries for `all`.
+    Filter { filter: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, strum::Display)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum BuiltinAction {
+    /// Open the selection, but keep searching.
+    Open,
+    /// Open the folder containing the selection.
+    Reveal,
+    /// Switch to another location.
+    Menu,
+    /// Open the config file.
+    EditConfig,
+    /// Copy the full path of the selection to the clipboard.
+    CopyPath,
+    /// Copy the selection as a percent-encoded `file://` URI to the clipboard.
+    CopyUri,
+    /// Choose one of the `action_menu` actions for the selection.
+    Actions,
+    /// Open a terminal in the folder of the selection.
+    Terminal,
+    /// Choose the application used to open the selection.
+    OpenWith,
+    /// Open the selection in `editor`, `$VISUAL` or `$EDITOR`.
+    Editor,
+    /// Move the selection to the trash. Requires `allow_delete` on the location.
+    Trash,
+    /// Rename or move the selection. Requires `allow_delete` on the location.
+    Rename,
+    /// Pin the selection to the top of the list, or unpin it.
+    Pin,
+    /// List the entries again, rescanning the location and updating its cache file.
+    Reload,
+    /// List folders instead of files, or files instead of folders, until the search is closed.
+    ToggleMode,
+}
+
+impl Action {
+    /// A short description for the key hints of the search, like `switch location`.
+    pub fn hint(&self) -> String {
+        match self {
+            Action::Builtin(BuiltinAction::Menu) => "switch location".to_owned(),
+            Action::Builtin(BuiltinAction::EditConfig) => "config".to_owned(),
+            Action::Builtin(BuiltinAction::ToggleMode) => "files/folders".to_owned(),
+            Action::Command { command } => command.split_whitespace().next().unwrap_or_default().to_owned(),
+            action => action.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Builtin(action) => write!(f, "{}", action),
+            Action::Command { command } => write!(f, "{}", command),
+            Action::Filter { filter } => write!(f, "filter {}", filter),
         }
     }
 }
@@ -47,36 +625,439 @@ impl Default for Config {
         Config {
             locations: LinkedHashMap::new(),
             fd_flags: None,
-            fzf_flags: None
+            fd_binary: None,
+            fzf_binary: None,
+            fzf_flags: None,
+            open_rules: None,
+            keybindings: None,
+            action_menu: None,
+            editor: None,
+            editor_terminal: None,
+            recent_size: None,
+            prefetch: None,
+            picker: Picker::default(),
+            hotkey: None,
+            http_port: None,
+            http_allow_origin: None,
+            watch_interval: None,
+            script: None,
+            include: None,
+            default_location: None,
+            filters: None,
+            history: None,
+            log: None,
+            max_results: None,
+            ui: None,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    pub fn parse(self, s: &str) -> Result<Config> {
+        let mut config: Config = match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(s)?,
+            ConfigFormat::Toml => toml::from_str(s)?,
+        };
+        for (name, location) in config.locations.iter_mut() {
+            location.name = name.clone();
+        }
+        Ok(config)
+    }
+
+    pub fn serialize(self, config: &Config) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+        })
+    }
+}
+
+/// `default_location` which reopens the location used last.
+pub const LAST_LOCATION: &str = "last";
+
+impl Location {
+    /// The location searched in another mode. Its cache file is kept apart, in the `cache` folder as with
+    /// `cache_file: auto`, and created by the first search in that mode.
+    pub fn in_mode(&self, mode: LocationMode) -> Location {
+        if mode == self.mode {
+            return self.clone();
+        }
+        Location {
+            mode,
+            cache_file: self.cache_file.as_ref().map(|_| crate::cache::AUTO.to_owned()),
+            auto_cache: true,
+            // --update-cache only updates the cache of the configured mode, the reload key binding updates this one
+            cache_ttl: None,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+
+    /// The pseudo-location searching all locations of the group.
+    pub fn of_group(group: &str) -> Location {
+        Location {
+            path: String::new(),
+            mode: LocationMode::Group,
+            group: Some(group.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// The host and folder of a `remote` location, like `user@host` and `/srv/data`.
+    pub fn remote_parts(&self) -> Option<(&str, &str)> {
+        self.remote.as_deref().map(|remote| remote.split_once(':').unwrap_or((remote, ".")))
+    }
+}
+
 impl Config {
+    /// The names of all groups, in the order of their first location.
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = Vec::new();
+        for group in self.enabled_locations().filter_map(|(_, loc)| loc.group.as_ref()) {
+            if !groups.contains(group) {
+                groups.push(group.clone());
+            }
+        }
+        groups
+    }
+
+    /// The enabled locations of the group, without its pseudo-location.
+    pub fn members<'a>(&'a self, group: &'a str) -> impl Iterator<Item = (&'a String, &'a Location)> {
+        self.enabled_locations()
+            .filter(move |(_, loc)| loc.mode != LocationMode::Group && loc.group.as_deref() == Some(group))
+    }
+
+    /// The name of the location opened when none is given: the `default_location`, or else the first enabled one.
+    pub fn default_location(&self) -> Result<&str> {
+        match self.default_location.as_deref() {
+            Some(LAST_LOCATION) if !self.locations.contains_key(LAST_LOCATION) => {
+                let last = std::fs::read_to_string(Self::last_location_path()).unwrap_or_default();
+                if let Some(name) = self.locations.keys().find(|k| *k == last.trim()) {
+                    return Ok(name);
+                }
+            },
+            Some(name) if self.locations.contains_key(name) => return Ok(name),
+            Some(name) => return Err(BlinkError::LocationNotFound(name.to_owned()).into()),
+            None => (),
+        }
+        self.enabled_locations().next()
+            .map(|(name, _)| name.as_str())
+            .ok_or_else(|| anyhow::anyhow!("All locations are disabled"))
+    }
+
+    /// The name of the location with this name or alias.
+    pub fn find_location(&self, name: &str) -> Option<&str> {
+        match self.locations.contains_key(name) {
+            true => self.locations.keys().find(|k| *k == name),
+            false => self.locations.iter()
+                .find(|(_, loc)| loc.aliases.iter().flatten().any(|alias| alias == name))
+                .map(|(k, _)| k),
+        }.map(|k| k.as_str())
+    }
+
+    /// The `history` settings for the location, or for the menu without a location.
+    pub fn history_settings(&self, location: Option<&Location>) -> HistorySettings {
+        let global = self.history.unwrap_or_default();
+        let local = location.and_then(|l| l.history).unwrap_or_default();
+        HistorySettings {
+            enabled: local.enabled.or(global.enabled),
+            max_entries: local.max_entries.or(global.max_entries),
+        }
+    }
+
+    /// The most entries shown in the search of the location.
+    pub fn max_results(&self, location: &Location) -> Option<usize> {
+        location.max_results.or(self.max_results)
+    }
+
+    /// The patterns of the named filter. `all` has none, unless the config defines such a filter.
+    pub fn filter_patterns(&self, name: &str) -> Result<Vec<String>> {
+        match self.filters.as_ref().and_then(|filters| filters.get(name)) {
+            Some(patterns) => Ok(patterns.clone()),
+            None if name == "all" => Ok(Vec::new()),
+            None => Err(anyhow::anyhow!("No filter named {}", name)),
+        }
+    }
+
+    /// The locations shown in menus and chosen by default, i.e. all without `enabled: false`.
+    pub fn enabled_locations(&self) -> impl Iterator<Item = (&String, &Location)> {
+        self.locations.iter().filter(|(_, loc)| loc.is_enabled())
+    }
+
+    /// The default key bindings, overridden by the `keybindings` section.
+    pub fn keybindings(&self) -> LinkedHashMap<String, Action> {
+        let mut bindings: LinkedHashMap<String, Action> = [
+            ("tab", BuiltinAction::Menu),
+            ("ctrl-x", BuiltinAction::Reveal),
+            ("alt-c", BuiltinAction::EditConfig),
+            ("ctrl-o", BuiltinAction::Actions),
+            ("alt-e", BuiltinAction::Editor),
+            ("f2", BuiltinAction::Rename),
+            ("alt-p", BuiltinAction::Pin),
+            ("ctrl-r", BuiltinAction::Reload),
+            ("ctrl-d", BuiltinAction::ToggleMode),
+        ].into_iter().map(|(key, action)| (key.to_owned(), Action::Builtin(action))).collect();
+        for (key, action) in self.keybindings.iter().flatten() {
+            bindings.insert(key.clone(), action.clone());
+        }
+        bindings
+    }
+
+    pub fn action_menu(&self) -> Vec<Action> {
+        match &self.action_menu {
+            Some(actions) => actions.clone(),
+            None => [
+                BuiltinAction::Open,
+                BuiltinAction::Reveal,
+                BuiltinAction::Editor,
+                BuiltinAction::CopyPath,
+                BuiltinAction::CopyUri,
+                BuiltinAction::Terminal,
+                BuiltinAction::OpenWith,
+            ].into_iter().map(Action::Builtin).collect(),
+        }
+    }
+
+    /// The folder of the config, histories and caches. Relative without a home folder, which [`Config::check_base_dir`] refuses.
     pub fn base_dir() -> PathBuf {
-        BaseDirs::new().unwrap().config_dir()
+        BaseDirs::new().map(|dirs| dirs.config_dir().to_owned()).unwrap_or_default()
             .join("blink-search")
     }
 
-    pub fn path() -> PathBuf {
+    /// Fails without a home folder, e.g. in a service without HOME.
+    pub fn check_base_dir() -> Result<()> {
+        BaseDirs::new().map(drop).ok_or_else(|| anyhow::anyhow!("No home folder found for the config, set HOME"))
+    }
+
+    fn last_location_path() -> PathBuf {
+        Self::base_dir().join("last-location.txt")
+    }
+
+    /// Remembers the location for `default_location: last`.
+    pub fn remember_location(&self, name: &str) -> Result<()> {
+        if self.default_location.as_deref() == Some(LAST_LOCATION) {
+            std::fs::write(Self::last_location_path(), name)?;
+        }
+        Ok(())
+    }
+
+    pub fn yaml_path() -> PathBuf {
         Self::base_dir().join("blink.yml")
     }
 
+    pub fn toml_path() -> PathBuf {
+        Self::base_dir().join("blink.toml")
+    }
+
+    /// The active config file. `blink.toml` takes precedence over `blink.yml` if both exist.
+    pub fn path() -> PathBuf {
+        let toml_path = Self::toml_path();
+        if toml_path.exists() { toml_path } else { Self::yaml_path() }
+    }
+
     pub fn new() -> Result<Self> {
         let path = Self::path();
         if !path.exists() {
             println!("Creating new config file: {}", path.to_string_lossy());
 
-            std::fs::create_dir(Self::base_dir())?;
+            std::fs::create_dir_all(Self::base_dir())?;
 
             let config = Config::default();
-            let config_str = serde_yaml::to_string(&config)?;
+            let config_str = ConfigFormat::from_path(&path).serialize(&config)?;
             std::fs::write(&path, config_str)?;
             Ok(config)
         } else {
-            let config_str = std::fs::read_to_string(&path)?;
-            let config = serde_yaml::from_str(&config_str)?;
-            Ok(config)
+            Self::load(&path)
         }
     }
+
+    /// Reads the config file and adds the locations of its `include` files.
+    /// Locations of later files replace those with the same name.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut config = Self::read(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for include in config.include.iter().flatten() {
+            let include_path = dir.join(include);
+            if !include_path.exists() {
+                debug!("Skipping missing include {}", include_path.to_string_lossy());
+                continue;
+            }
+            for (name, location) in Self::read(&include_path)?.locations {
+                config.locations.insert(name, location);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads only this config file, without its `include` files.
+    fn read(path: &Path) -> Result<Self> {
+        let invalid = |reason: String| BlinkError::ConfigInvalid { path: path.to_owned(), reason };
+        let config_str = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        Ok(ConfigFormat::from_path(path).parse(&config_str).map_err(|e| invalid(e.to_string()))?)
+    }
+
+    /// Converts `blink.yml` into `blink.toml`. The YAML file is kept as `blink.yml.bak`.
+    pub fn migrate_to_toml() -> Result<PathBuf> {
+        let yaml_path = Self::yaml_path();
+        let toml_path = Self::toml_path();
+        if toml_path.exists() {
+            return Err(anyhow::anyhow!("{} already exists", toml_path.to_string_lossy()));
+        }
+        if !yaml_path.exists() {
+            return Err(anyhow::anyhow!("{} not found", yaml_path.to_string_lossy()));
+        }
+        // the included files stay separate
+        let config = Self::read(&yaml_path)?;
+        std::fs::write(&toml_path, ConfigFormat::Toml.serialize(&config)?)?;
+        std::fs::rename(&yaml_path, yaml_path.with_extension("yml.bak"))?;
+        Ok(toml_path)
+    }
+}
+
+#[test]
+fn toml_roundtrip() {
+    let mut config = Config::default();
+    config.locations.insert("nas".into(), Location {
+        path: "\\\\nas.local\\share".into(),
+        mode: LocationMode::Folders,
+        cache_file: Some(".blink\\all-folders.txt".into()),
+        cache_ttl: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        scan_timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    });
+    config.fd_flags = Some(vec!["--hidden".into()]);
+
+    let s = ConfigFormat::Toml.serialize(&config).unwrap();
+    let parsed = ConfigFormat::Toml.parse(&s).unwrap();
+    let nas = parsed.locations.get("nas").unwrap();
+    assert_eq!(nas.path, "\\\\nas.local\\share");
+    assert_eq!(nas.cache_file.as_deref(), Some(".blink\\all-folders.txt"));
+    assert_eq!(nas.cache_ttl, Some(Duration::from_secs(7 * 24 * 60 * 60)));
+    assert_eq!(nas.scan_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(parsed.fd_flags, Some(vec!["--hidden".to_owned()]));
+}
+
+#[test]
+fn includes_add_locations() {
+    let dir = std::env::temp_dir().join(format!("blink-include-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("blink.yml"), "
+locations:
+  docs: { path: /docs, mode: files }
+  nas: { path: /nas, mode: folders }
+include: [work.toml, missing.yml]
+").unwrap();
+    std::fs::write(dir.join("work.toml"), "
+[locations.nas]
+path = '/mnt/nas'
+mode = 'folders'
+
+[locations.wiki]
+path = '/wiki'
+mode = 'files'
+").unwrap();
+    let config = Config::load(&dir.join("blink.yml")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let locations: Vec<_> = config.locations.iter().map(|(name, loc)| (name.as_str(), loc.path.as_str())).collect();
+    assert_eq!(locations, [("docs", "/docs"), ("nas", "/mnt/nas"), ("wiki", "/wiki")]);
+}
+
+#[test]
+fn default_location() {
+    let mut config = ConfigFormat::Yaml.parse("
+locations:
+  old: { path: /old, mode: files, enabled: false }
+  docs: { path: /docs, mode: files }
+  nas: { path: /nas, mode: folders }
+").unwrap();
+    assert_eq!(config.default_location().unwrap(), "docs");
+    config.default_location = Some("nas".into());
+    assert_eq!(config.default_location().unwrap(), "nas");
+    config.default_location = Some("gone".into());
+    assert!(config.default_location().is_err());
+}
+
+#[test]
+fn history_settings_of_locations() {
+    let config = ConfigFormat::Yaml.parse("
+locations:
+  docs: { path: /docs, mode: files }
+  private: { path: /private, mode: files, history: { enabled: false } }
+history: { max_entries: 50 }
+").unwrap();
+    let docs = config.history_settings(config.locations.get("docs"));
+    assert_eq!((docs.enabled, docs.max_entries), (None, Some(50)));
+    let private = config.history_settings(config.locations.get("private"));
+    assert_eq!((private.enabled, private.max_entries), (Some(false), Some(50)));
+}
+
+#[test]
+fn keybindings_override_defaults() {
+    let config = ConfigFormat::Yaml.parse("
+locations: {}
+keybindings:
+  ctrl-x: open
+  ctrl-e:
+    command: code {path}
+  alt-i:
+    filter: images
+").unwrap();
+    let bindings = config.keybindings();
+    assert_eq!(bindings.get("tab"), Some(&Action::Builtin(BuiltinAction::Menu)));
+    assert_eq!(bindings.get("ctrl-x"), Some(&Action::Builtin(BuiltinAction::Open)));
+    assert_eq!(bindings.get("ctrl-e"), Some(&Action::Command { command: "code {path}".into() }));
+    assert_eq!(bindings.get("alt-i"), Some(&Action::Filter { filter: "images".into() }));
+}
+
+#[test]
+fn picker_kind_or_command() {
+    let config = ConfigFormat::Yaml.parse("locations: {}\npicker: rofi\n").unwrap();
+    assert_eq!(config.picker.kind(), PickerKind::Rofi);
+
+    let config = ConfigFormat::Yaml.parse("
+locations: {}
+picker:
+  binary: sk
+  bind_arg: --bind={key}:{action}
+").unwrap();
+    let picker = config.picker.command();
+    assert_eq!(config.picker.kind(), PickerKind::Fzf);
+    assert!(!config.picker.is_fzf());
+    assert_eq!(picker.bind_arg("tab", "accept").as_deref(), Some("--bind=tab:accept"));
+    assert_eq!(picker.history_arg(Path::new("h.txt")), None);
+}
+
+#[test]
+fn ui_settings_as_fzf_flags() {
+    let config = ConfigFormat::Yaml.parse("
+locations: {}
+ui:
+  height: 40%
+  layout: reverse
+  border: rounded
+  colors:
+    hl: 214
+    fg+: \"#ebdbb2:bold\"
+  prompt: \"{location} > \"
+").unwrap();
+    assert_eq!(config.ui.unwrap().fzf_args(Some("docs")),
+        ["--height=40%", "--layout=reverse", "--border=rounded", "--color=hl:214,fg+:#ebdbb2:bold", "--prompt=docs > "]);
+    assert!(ConfigFormat::Yaml.parse("locations: {}\nui:\n  height: 140%\n").is_err());
+    assert!(ConfigFormat::Yaml.parse("locations: {}\nui:\n  colors:\n    hl: \"red,bg:blue\"\n").is_err());
 }
@@ -0,0 +1,260 @@
+use std::{fs::File, io::{self, Read, Seek, Write}, path::Path};
+use anyhow::Result;
+
+/// Starts the encrypted cache files, followed by the version and the nonce of the stream.
+const MAGIC: &[u8] = b"BLINKENC";
+#[cfg(feature = "encryption")]
+const VERSION: u8 = 1;
+/// Size of the chunks the file is encrypted in, each of them authenticated on its own.
+#[cfg(feature = "encryption")]
+const CHUNK_SIZE: usize = 64 * 1024;
+#[cfg(feature = "encryption")]
+const TAG_SIZE: usize = 16;
+#[cfg(feature = "encryption")]
+const NONCE_SIZE: usize = 19;
+
+/// The keyring entry holding the key of the encrypted cache files, generated on first use.
+#[cfg(feature = "encryption")]
+const KEYRING_SERVICE: &str = "blink-search";
+#[cfg(feature = "encryption")]
+const KEYRING_USER: &str = "cache-key";
+
+/// A writer which completes its file once everything is written, like the last chunk of an encrypted file.
+pub trait FinishWrite: Write + Send {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl FinishWrite for File {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates the file, encrypting what is written to it if `encrypt` is set.
+pub fn create(path: &Path, encrypt: bool) -> Result<Box<dyn FinishWrite>> {
+    let file = File::create(path)?;
+    match encrypt {
+        true => encrypt_to(file).inspect_err(|_| {
+            let _ = std::fs::remove_file(path);
+        }),
+        false => Ok(Box::new(file)),
+    }
+}
+
+/// Opens the file, decrypting it if it is encrypted.
+pub fn open(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let mut file = File::open(path)?;
+    match is_encrypted(&mut file)? {
+        true => decrypt_from(file),
+        false => Ok(Box::new(file)),
+    }
+}
+
+/// Whether the file starts like an encrypted file. It is read from the start again afterwards.
+pub fn is_encrypted(file: &mut File) -> io::Result<bool> {
+    let mut start = Vec::with_capacity(MAGIC.len());
+    Read::by_ref(file).take(MAGIC.len() as u64).read_to_end(&mut start)?;
+    file.rewind()?;
+    Ok(start == MAGIC)
+}
+
+#[cfg(feature = "encryption")]
+pub use encryption::{decrypt_from, encrypt_to};
+
+#[cfg(not(feature = "encryption"))]
+pub fn encrypt_to(_file: File) -> Result<Box<dyn FinishWrite>> {
+    Err(anyhow::anyhow!("blink was built without the encryption feature"))
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn decrypt_from(_file: File) -> Result<Box<dyn Read + Send>> {
+    Err(anyhow::anyhow!("blink was built without the encryption feature"))
+}
+
+#[cfg(feature = "encryption")]
+mod encryption {
+    use std::{fs::File, io::{self, Read, Write}, sync::OnceLock};
+    use anyhow::Result;
+    use chacha20poly1305::aead::{rand_core::RngCore, stream::{DecryptorBE32, EncryptorBE32}, OsRng};
+    use chacha20poly1305::{Key, KeyInit, XChaCha20Poly1305};
+    use log::info;
+    use super::*;
+
+    /// The key of the encrypted files, read from the keyring of the system once.
+    fn key() -> Result<Key> {
+        static KEY: OnceLock<Key> = OnceLock::new();
+        if let Some(key) = KEY.get() {
+            return Ok(*key);
+        }
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+        let hex = match entry.get_password() {
+            Ok(hex) => hex,
+            Err(keyring::Error::NoEntry) => {
+                info!("Creating the cache key in the keyring");
+                let hex: String = XChaCha20Poly1305::generate_key(&mut OsRng).iter().map(|b| format!("{:02x}", b)).collect();
+                entry.set_password(&hex)?;
+                hex
+            },
+            Err(e) => return Err(anyhow::anyhow!("Cannot read the cache key from the keyring: {}", e)),
+        };
+        let bytes = (0..hex.len()).step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or_else(|| anyhow::anyhow!("The cache key in the keyring is invalid"))?;
+        Ok(*KEY.get_or_init(|| *Key::from_slice(&bytes)))
+    }
+
+    pub fn encrypt_to(file: File) -> Result<Box<dyn FinishWrite>> {
+        Ok(Box::new(EncryptWriter::new(io::BufWriter::new(file), &key()?)?))
+    }
+
+    pub fn decrypt_from(file: File) -> Result<Box<dyn Read + Send>> {
+        Ok(Box::new(DecryptReader::new(io::BufReader::new(file), &key()?)?))
+    }
+
+    fn aead_error(_: chacha20poly1305::Error) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "the encrypted file is damaged or was encrypted with another key")
+    }
+
+    /// Encrypts what is written in chunks, the last of which is marked as such when finished.
+    pub(super) struct EncryptWriter<W: Write> {
+        out: W,
+        encryptor: Option<EncryptorBE32<XChaCha20Poly1305>>,
+        chunk: Vec<u8>,
+    }
+
+    impl<W: Write> EncryptWriter<W> {
+        pub(super) fn new(mut out: W, key: &Key) -> io::Result<Self> {
+            let mut nonce = [0; NONCE_SIZE];
+            OsRng.fill_bytes(&mut nonce);
+            out.write_all(MAGIC)?;
+            out.write_all(&[VERSION])?;
+            out.write_all(&nonce)?;
+            let encryptor = EncryptorBE32::from_aead(XChaCha20Poly1305::new(key), (&nonce).into());
+            Ok(EncryptWriter { out, encryptor: Some(encryptor), chunk: Vec::with_capacity(CHUNK_SIZE) })
+        }
+
+        /// Writes the last chunk, returning the writer of the encrypted file.
+        pub(super) fn into_inner(mut self) -> io::Result<W> {
+            let encryptor = self.encryptor.take().ok_or_else(|| io::Error::other("already finished"))?;
+            let encrypted = encryptor.encrypt_last(self.chunk.as_slice()).map_err(aead_error)?;
+            self.out.write_all(&encrypted)?;
+            self.out.flush()?;
+            Ok(self.out)
+        }
+    }
+
+    impl<W: Write> Write for EncryptWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            // a full chunk is only written once more follows, as the last one is encrypted differently
+            if self.chunk.len() == CHUNK_SIZE {
+                let encryptor = self.encryptor.as_mut().ok_or_else(|| io::Error::other("already finished"))?;
+                let encrypted = encryptor.encrypt_next(self.chunk.as_slice()).map_err(aead_error)?;
+                self.out.write_all(&encrypted)?;
+                self.chunk.clear();
+            }
+            let len = buf.len().min(CHUNK_SIZE - self.chunk.len());
+            self.chunk.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.out.flush()
+        }
+    }
+
+    impl<W: Write + Send> FinishWrite for EncryptWriter<W> {
+        fn finish(self: Box<Self>) -> io::Result<()> {
+            self.into_inner().map(drop)
+        }
+    }
+
+    /// Decrypts a file written by [`EncryptWriter`], failing if it was changed or cut off.
+    pub(super) struct DecryptReader<R: Read> {
+        input: R,
+        decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+        /// The encrypted chunk read ahead, to tell whether the current one is the last
+        next: Vec<u8>,
+        chunk: Vec<u8>,
+        pos: usize,
+    }
+
+    impl<R: Read> DecryptReader<R> {
+        pub(super) fn new(mut input: R, key: &Key) -> io::Result<Self> {
+            let mut header = [0; MAGIC.len() + 1 + NONCE_SIZE];
+            input.read_exact(&mut header)?;
+            if &header[..MAGIC.len()] != MAGIC {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "not an encrypted file"));
+            }
+            if header[MAGIC.len()] != VERSION {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown version {} of the encrypted file", header[MAGIC.len()])));
+            }
+            let nonce = &header[MAGIC.len() + 1..];
+            let decryptor = DecryptorBE32::from_aead(XChaCha20Poly1305::new(key), nonce.into());
+            let mut reader = DecryptReader { input, decryptor: Some(decryptor), next: Vec::new(), chunk: Vec::new(), pos: 0 };
+            reader.next = reader.read_chunk()?;
+            Ok(reader)
+        }
+
+        /// Reads the next encrypted chunk, which is shorter than a full one only at the end of the file.
+        fn read_chunk(&mut self) -> io::Result<Vec<u8>> {
+            let mut chunk = Vec::with_capacity(CHUNK_SIZE + TAG_SIZE);
+            Read::by_ref(&mut self.input).take((CHUNK_SIZE + TAG_SIZE) as u64).read_to_end(&mut chunk)?;
+            Ok(chunk)
+        }
+
+        fn decrypt_next(&mut self) -> io::Result<()> {
+            let encrypted = std::mem::take(&mut self.next);
+            self.next = self.read_chunk()?;
+            self.chunk = match self.next.is_empty() {
+                true => match self.decryptor.take() {
+                    Some(decryptor) => decryptor.decrypt_last(encrypted.as_slice()).map_err(aead_error)?,
+                    None => Vec::new(),
+                },
+                false => self.decryptor.as_mut()
+                    .ok_or_else(|| io::Error::other("already finished"))?
+                    .decrypt_next(encrypted.as_slice()).map_err(aead_error)?,
+            };
+            self.pos = 0;
+            Ok(())
+        }
+    }
+
+    impl<R: Read> Read for DecryptReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while self.pos == self.chunk.len() {
+                if self.decryptor.is_none() {
+                    return Ok(0);
+                }
+                self.decrypt_next()?;
+            }
+            let len = buf.len().min(self.chunk.len() - self.pos);
+            buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypted_roundtrip() {
+    use encryption::{DecryptReader, EncryptWriter};
+    let key = chacha20poly1305::Key::from([7; 32]);
+    for size in [0, 10, 64 * 1024, 200 * 1024 + 3] {
+        let plain: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let mut writer = EncryptWriter::new(Vec::new(), &key).unwrap();
+        writer.write_all(&plain).unwrap();
+        let mut encrypted = writer.into_inner().unwrap();
+        assert!(encrypted.starts_with(MAGIC));
+
+        let mut decrypted = Vec::new();
+        DecryptReader::new(encrypted.as_slice(), &key).unwrap().read_to_end(&mut decrypted).unwrap();
+        assert_eq!(decrypted, plain);
+
+        // cut off files are refused as well as changed ones
+        encrypted.truncate(encrypted.len() - 1);
+        assert!(DecryptReader::new(encrypted.as_slice(), &key).and_then(|mut r| r.read_to_end(&mut Vec::new())).is_err());
+    }
+}
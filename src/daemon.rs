@@ -0,0 +1,202 @@
+use std::{collections::HashMap, io::{BufRead, BufReader, Write}, path::PathBuf, sync::{Arc, RwLock}, thread, time::{Duration, Instant}};
+use anyhow::Result;
+use interprocess::local_socket::{prelude::*, ListenerOptions, Stream};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use blink_search::config::{Config, Location};
+use blink_search::error::BlinkError;
+use blink_search::index::{entry_path, read_location};
+use blink_search::{cache, paths, search};
+use crate::{ipc, launcher};
+
+/// The socket `bl daemon` answers queries on.
+pub const SERVICE: &str = "blink-search-daemon";
+/// How often the `watch` locations are rescanned, unless `watch_interval` is set.
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+/// How often the daemon logs that it is still running.
+const HEARTBEAT: Duration = Duration::from_secs(60);
+/// Most results of plain queries.
+const PLAIN_LIMIT: usize = 20;
+
+/// A query sent to the daemon as one line of JSON, like `{"location": "nas", "query": "report", "limit": 20}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Query {
+    pub location: String,
+    pub query: String,
+    pub limit: usize,
+}
+
+/// The answer to a [`Query`], one line of JSON like `{"results": [...]}` or `{"error": "..."}`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Answer {
+    Results(Vec<Match>),
+    Error(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Match {
+    pub entry: String,
+    pub path: String,
+    pub score: i64,
+}
+
+/// The entries of the `watch` locations, by location name.
+pub type Indexes = Arc<RwLock<HashMap<String, Vec<PathBuf>>>>;
+
+/// Updates the cache file of the location, if it has one, and reads its entries.
+fn refresh(name: &str, location: &Location, config: &Config) -> Result<Vec<PathBuf>> {
+    if cache::cache_path(location).is_some() {
+        if let Err(e) = cache::update(location, config) {
+            warn!("Cannot update the cache of {}, reading the old one: {}", name, e);
+        }
+    }
+    BufReader::new(read_location(name, location, config)?).split(b'\n')
+        .map(|entry| Ok(paths::from_bytes(entry?)))
+        .collect()
+}
+
+/// The best matches of the location for the query, from memory if it is watched.
+pub fn search(indexes: &Indexes, location_name: &str, query: &str, limit: usize, config: &Config) -> Result<Vec<Match>> {
+    let name = config.find_location(location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.to_owned()))?;
+    let location = &config.locations[name];
+    let indexed = indexes.read().unwrap().get(name).map(|entries| search::search_entries(entries, query, limit));
+    // other locations are read for each query, like `bl --query` does
+    let results = match indexed {
+        Some(results) => results,
+        None => search::search(name, location, config, query, limit)?,
+    };
+    Ok(results.into_iter()
+        .map(|(entry, score)| Match {
+            path: entry_path(location, &entry, config).to_string_lossy().into_owned(),
+            entry: entry.to_string_lossy().into_owned(),
+            score,
+        })
+        .collect())
+}
+
+fn answer(line: &str, indexes: &Indexes, config: &Config) -> Result<Vec<Match>> {
+    let query: Query = serde_json::from_str(line)?;
+    debug!("Daemon query {:?}", query);
+    search(indexes, &query.location, &query.query, query.limit, config)
+}
+
+/// Answers a plain query like `nas quarterly report`, for scripts without a JSON parser like AutoHotkey:
+/// the full paths of the best matches, one per line, followed by an empty line. Searches the default location
+/// unless the first word names one, like the Flow Launcher plugin.
+fn plain_answer(line: &str, indexes: &Indexes, config: &Config) -> String {
+    let (location, query) = launcher::split_query(line, config);
+    debug!("Daemon plain query {:?} in {}", query, location);
+    match search(indexes, location, query, PLAIN_LIMIT, config) {
+        Ok(matches) => matches.into_iter().map(|m| m.path + "\n").collect(),
+        Err(e) => format!("error: {}\n", e),
+    }
+}
+
+/// Answers the queries of a client, one per line, until it disconnects.
+fn serve_client(conn: Stream, indexes: &Indexes, config: &Config) {
+    let mut conn = BufReader::new(conn);
+    let mut line = String::new();
+    while matches!(conn.read_line(&mut line), Ok(n) if n > 0) {
+        let answer = match line.trim_start().starts_with('{') {
+            true => match answer(&line, indexes, config) {
+                Ok(matches) => serde_json::to_string(&Answer::Results(matches)),
+                Err(e) => serde_json::to_string(&Answer::Error(e.to_string())),
+            },
+            false => Ok(plain_answer(line.trim(), indexes, config)),
+        };
+        let Ok(answer) = answer else { return };
+        if writeln!(conn.get_mut(), "{}", answer).is_err() {
+            return;
+        }
+        line.clear();
+    }
+}
+
+/// Sends the query to the running daemon. Returns `None` if there is none.
+pub fn query(query: &Query) -> Option<Result<Vec<Match>>> {
+    let conn = Stream::connect(ipc::socket_name(SERVICE).ok()?).ok()?;
+    let send = || -> Result<Vec<Match>> {
+        let mut conn = BufReader::new(conn);
+        writeln!(conn.get_mut(), "{}", serde_json::to_string(query)?)?;
+        let mut line = String::new();
+        conn.read_line(&mut line)?;
+        match serde_json::from_str(&line)? {
+            Answer::Results(matches) => Ok(matches),
+            Answer::Error(e) => Err(anyhow::anyhow!(e)),
+        }
+    };
+    Some(send())
+}
+
+/// Keeps the entries of the `watch` locations in memory, rescanning them every `watch_interval`,
+/// and answers the queries of `bl query` until killed.
+pub fn run(config: &Config) -> Result<()> {
+    if Stream::connect(ipc::socket_name(SERVICE)?).is_ok() {
+        return Err(anyhow::anyhow!("bl daemon is already running"));
+    }
+    let listener = ListenerOptions::new().name(ipc::socket_name(SERVICE)?).try_overwrite(true).create_sync()?;
+    let watched: Vec<(&String, &Location)> = config.enabled_locations().filter(|(_, loc)| loc.watch).collect();
+    if watched.is_empty() {
+        println!("No location has watch: true, each query reads its location");
+    }
+    let interval = config.watch_interval.unwrap_or(DEFAULT_WATCH_INTERVAL);
+    let indexes: Indexes = Arc::default();
+    #[cfg(all(feature = "dbus", target_os = "linux"))]
+    if let Err(e) = crate::dbus::serve(indexes.clone(), config.clone()) {
+        warn!("Cannot offer the search on D-Bus: {}", e);
+    }
+    info!("Daemon started, watching {} locations", watched.len());
+    println!("Watching {} locations, answering bl query", watched.len());
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut refreshed: Option<Instant> = None;
+            loop {
+                if refreshed.is_none_or(|t| t.elapsed() >= interval) {
+                    refreshed = Some(Instant::now());
+                    for (name, location) in &watched {
+                        let start = Instant::now();
+                        match refresh(name, location, config) {
+                            Ok(entries) => {
+                                info!("Daemon loaded {} entries of {} in {:.1?}", entries.len(), name, start.elapsed());
+                                indexes.write().unwrap().insert(name.to_string(), entries);
+                            },
+                            // the entries of the last scan are kept
+                            Err(e) => warn!("Daemon cannot read {}: {}", name, e),
+                        }
+                    }
+                }
+                let count: usize = indexes.read().unwrap().values().map(Vec::len).sum();
+                info!("Daemon running, {} entries of {} locations in memory", count, indexes.read().unwrap().len());
+                thread::sleep(HEARTBEAT.min(interval));
+            }
+        });
+        for conn in listener.incoming().filter_map(|c| c.ok()) {
+            let indexes = &indexes;
+            s.spawn(move || serve_client(conn, indexes, config));
+        }
+    });
+    Ok(())
+}
+
+#[test]
+fn answer_lines() {
+    let answer: Answer = serde_json::from_str(r#"{"results": [{"entry": "a.pdf", "path": "/docs/a.pdf", "score": 1}]}"#).unwrap();
+    assert!(matches!(answer, Answer::Results(matches) if matches[0].path == "/docs/a.pdf"));
+    assert_eq!(serde_json::to_string(&Answer::Error("x".into())).unwrap(), r#"{"error":"x"}"#);
+}
+
+#[test]
+fn plain_answers() {
+    let config = blink_search::config::ConfigFormat::Yaml.parse("
+locations:
+  docs: { path: /docs, mode: files, watch: true }
+").unwrap();
+    let indexes: Indexes = Arc::default();
+    indexes.write().unwrap().insert("docs".to_owned(), vec![PathBuf::from("2024/report.pdf"), PathBuf::from("notes.txt")]);
+    let path = blink_search::index::entry_path(&config.locations["docs"], std::path::Path::new("2024/report.pdf"), &config);
+    assert_eq!(plain_answer("docs report", &indexes, &config), format!("{}\n", path.to_string_lossy()));
+    assert_eq!(plain_answer("report", &indexes, &config), format!("{}\n", path.to_string_lossy()));
+    assert_eq!(plain_answer("docs missing", &indexes, &config), "");
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, path::Path, sync::Arc, thread};
+use anyhow::Result;
+use log::debug;
+use zbus::{blocking::connection, fdo, interface, zvariant::Value};
+use blink_search::config::{Config, LocationMode};
+use blink_search::index::entry_path;
+use crate::daemon::{self, Indexes, Match};
+use crate::{launcher, rpc};
+
+/// The bus name of `bl daemon` on the session bus.
+pub const BUS_NAME: &str = "org.blinksearch.Search";
+const OBJECT_PATH: &str = "/org/blinksearch/Search1";
+/// Most results shown by the search of the desktop.
+const DESKTOP_LIMIT: usize = 20;
+
+/// Searches the locations of the daemon for the D-Bus interfaces.
+#[derive(Clone)]
+struct Searcher {
+    indexes: Indexes,
+    config: Arc<Config>,
+}
+
+impl Searcher {
+    fn search(&self, location: &str, query: &str, limit: usize) -> fdo::Result<Vec<Match>> {
+        daemon::search(&self.indexes, location, query, limit, &self.config).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Searches like the Flow Launcher plugin: the first word may name the location, otherwise the default one is searched.
+    /// The ids of the results hold the location and the entry, separated by a tab.
+    fn search_text(&self, text: &str) -> fdo::Result<Vec<(String, Match)>> {
+        let (location, query) = launcher::split_query(text, &self.config);
+        if location.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self.search(location, query, DESKTOP_LIMIT)?.into_iter()
+            .map(|m| (format!("{}\t{}", location, m.entry), m))
+            .collect())
+    }
+
+    fn open(&self, id: &str) -> fdo::Result<()> {
+        let (location, entry) = id.split_once('\t').ok_or_else(|| fdo::Error::InvalidArgs(format!("Invalid result id {}", id)))?;
+        self.open_entry(location, entry)
+    }
+
+    fn open_entry(&self, location: &str, entry: &str) -> fdo::Result<()> {
+        rpc::open_entry(location, entry, &self.config).map(drop).map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// The full path of the entry of a result id.
+    fn path(&self, id: &str) -> String {
+        let Some((location, entry)) = id.split_once('\t') else { return String::new() };
+        match self.config.locations.get(location) {
+            Some(location) => entry_path(location, Path::new(entry), &self.config).to_string_lossy().into_owned(),
+            None => entry.to_owned(),
+        }
+    }
+
+    fn is_folder(&self, id: &str) -> bool {
+        let location = id.split_once('\t').map(|(location, _)| location).unwrap_or_default();
+        self.config.locations.get(location).is_some_and(|loc| loc.mode == LocationMode::Folders) || id.ends_with('/')
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| path.to_owned())
+}
+
+/// The search of blink for scripts, like `bl query`.
+struct Search1(Searcher);
+
+#[interface(name = "org.blinksearch.Search1")]
+impl Search1 {
+    /// The best matches as entry, full path and score.
+    fn search(&self, location: &str, query: &str, limit: u32) -> fdo::Result<Vec<(String, String, i64)>> {
+        Ok(self.0.search(location, query, limit as usize)?.into_iter().map(|m| (m.entry, m.path, m.score)).collect())
+    }
+
+    fn open(&self, location: &str, entry: &str) -> fdo::Result<()> {
+        self.0.open_entry(location, entry)
+    }
+}
+
+/// The search provider of GNOME Shell, registered by a file in `gnome-shell/search-providers`.
+struct GnomeSearchProvider(Searcher);
+
+#[interface(name = "org.gnome.Shell.SearchProvider2")]
+impl GnomeSearchProvider {
+    fn get_initial_result_set(&self, terms: Vec<String>) -> fdo::Result<Vec<String>> {
+        Ok(self.0.search_text(&terms.join(" "))?.into_iter().map(|(id, _)| id).collect())
+    }
+
+    fn get_subsearch_result_set(&self, _previous_results: Vec<String>, terms: Vec<String>) -> fdo::Result<Vec<String>> {
+        self.get_initial_result_set(terms)
+    }
+
+    fn get_result_metas(&self, ids: Vec<String>) -> Vec<HashMap<String, Value<'static>>> {
+        ids.into_iter()
+            .map(|id| {
+                let path = self.0.path(&id);
+                let icon = if self.0.is_folder(&id) { "folder" } else { "text-x-generic" };
+                HashMap::from([
+                    ("name".to_owned(), Value::from(file_name(&path))),
+                    ("description".to_owned(), Value::from(path)),
+                    ("gicon".to_owned(), Value::from(icon)),
+                    ("id".to_owned(), Value::from(id)),
+                ])
+            })
+            .collect()
+    }
+
+    fn activate_result(&self, id: &str, _terms: Vec<String>, _timestamp: u32) -> fdo::Result<()> {
+        self.0.open(id)
+    }
+
+    fn launch_search(&self, terms: Vec<String>, _timestamp: u32) {
+        debug!("Ignoring LaunchSearch for {:?}", terms);
+    }
+}
+
+/// The D-Bus runner of KRunner, registered by a file in `krunner/dbusplugins`.
+struct KRunner(Searcher);
+
+/// The match type of KRunner for results which may be what is searched for.
+const POSSIBLE_MATCH: i32 = 30;
+
+#[interface(name = "org.kde.krunner1")]
+impl KRunner {
+    fn actions(&self) -> Vec<(String, String, String)> {
+        Vec::new()
+    }
+
+    #[allow(clippy::type_complexity)]
+    #[zbus(name = "Match")]
+    fn match_query(&self, query: &str) -> fdo::Result<Vec<(String, String, String, i32, f64, HashMap<String, Value<'static>>)>> {
+        let results = self.0.search_text(query)?;
+        let count = results.len().max(1) as f64;
+        Ok(results.into_iter().enumerate()
+            .map(|(i, (id, m))| {
+                let icon = if self.0.is_folder(&id) { "folder" } else { "text-x-generic" };
+                // KRunner sorts by relevance, which keeps the order of blink
+                let relevance = 1.0 - i as f64 / count / 2.0;
+                (id, file_name(&m.path), icon.to_owned(), POSSIBLE_MATCH, relevance, HashMap::from([("subtext".to_owned(), Value::from(m.path))]))
+            })
+            .collect())
+    }
+
+    fn run(&self, match_id: &str, _action_id: &str) -> fdo::Result<()> {
+        self.0.open(match_id)
+    }
+}
+
+/// Offers the search of the daemon on the session bus as `org.blinksearch.Search`, with the interfaces of
+/// blink, GNOME Shell search providers and KRunner.
+pub fn serve(indexes: Indexes, config: Config) -> Result<()> {
+    let searcher = Searcher { indexes, config: Arc::new(config) };
+    let connection = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Search1(searcher.clone()))?
+        .serve_at(OBJECT_PATH, GnomeSearchProvider(searcher.clone()))?
+        .serve_at(OBJECT_PATH, KRunner(searcher))?
+        .build()?;
+    debug!("Offering {} on D-Bus", BUS_NAME);
+    // the requests are answered by the executor of the connection, as long as it is open
+    thread::spawn(move || {
+        let _connection = connection;
+        loop {
+            thread::park();
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::process::Command;
+use anyhow::Result;
+use blink_search::config::PickerKind;
+use blink_search::error::BlinkError;
+use crate::picker::{run_picker, Choice, Entries, Pick, Picker};
+
+/// First entry of the list, which switches to the location menu.
+/// dmenu-like pickers cannot bind keys, so this replaces the `menu` key binding.
+const MENU_ENTRY: &str = "» switch location";
+
+fn picker_cmd(picker: PickerKind, prompt: &str) -> Command {
+    match picker {
+        PickerKind::Rofi => {
+            let mut cmd = Command::new("rofi");
+            cmd.arg("-dmenu").arg("-i").arg("-p").arg(prompt);
+            cmd
+        },
+        PickerKind::Wofi => {
+            let mut cmd = Command::new("wofi");
+            cmd.arg("--dmenu").arg("--insensitive").arg("--prompt").arg(prompt);
+            cmd
+        },
+        PickerKind::Dmenu | PickerKind::Fzf => {
+            let mut cmd = Command::new("dmenu");
+            cmd.arg("-i").arg("-p").arg(prompt);
+            cmd
+        },
+    }
+}
+
+/// rofi, wofi or dmenu. Only rofi supports starting the location menu with a query, the others ignore it.
+pub struct Dmenu(pub PickerKind);
+
+impl Picker for Dmenu {
+    fn pick(&self, pick: Pick, entries: Entries) -> Result<Choice> {
+        let picker = self.0;
+        let search = matches!(pick, Pick::Search { .. });
+        let (mut cmd, entries) = match pick {
+            Pick::Search { name, .. } => {
+                let entries: Entries = Box::new(move |out| {
+                    writeln!(out, "{}", MENU_ENTRY)?;
+                    entries(out)
+                });
+                (picker_cmd(picker, name), entries)
+            },
+            Pick::Locations { query } => {
+                let mut cmd = picker_cmd(picker, "location");
+                if let (PickerKind::Rofi, Some(q)) = (picker, query) {
+                    cmd.arg("-filter").arg(q);
+                }
+                (cmd, entries)
+            },
+            Pick::Actions { .. } => (picker_cmd(picker, "action"), entries),
+            Pick::Folder => (picker_cmd(picker, "folder"), entries),
+            Pick::Again { .. } => {
+                let mut cmd = picker_cmd(picker, "open again");
+                if picker == PickerKind::Rofi {
+                    cmd.arg("-multi-select");
+                }
+                (cmd, entries)
+            },
+        };
+
+        let again = matches!(pick, Pick::Again { .. });
+        let (code, lines) = run_picker(&mut cmd, entries, search)?;
+        if again && code == Some(0) && !lines.is_empty() {
+            return Ok(Choice::Entries(lines));
+        }
+        match (code, lines.into_iter().next()) {
+            (Some(0), Some(s)) if s == MENU_ENTRY.as_bytes() => Ok(Choice::Menu(None)),
+            (Some(0), Some(s)) => Ok(Choice::Entry(s)),
+            (Some(0 | 1) | None, _) => Err(BlinkError::Aborted.into()),
+            (code, _) => Err(anyhow::anyhow!("{:?} exited with code {:?}", picker, code)),
+        }
+    }
+}
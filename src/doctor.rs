@@ -0,0 +1,228 @@
+use std::{fmt::Display, path::Path, process::{Command, Stdio}, time::Duration};
+use anyhow::Result;
+use blink_search::config::{Config, FinderKind, Location, LocationMode, PickerKind};
+use blink_search::{cache, find_program, finder, open, tool};
+
+/// Collects the results of `bl doctor`, printing one line per check.
+#[derive(Default)]
+struct Report {
+    problems: usize,
+}
+
+impl Report {
+    fn section(&self, title: &str) {
+        println!("{}", title);
+    }
+
+    fn ok(&self, check: &str, detail: impl Display) {
+        println!("  ok    {:<20} {}", check, detail);
+    }
+
+    fn warn(&self, check: &str, detail: impl Display) {
+        println!("  warn  {:<20} {}", check, detail);
+    }
+
+    fn fail(&mut self, check: &str, detail: impl Display) {
+        self.problems += 1;
+        println!("  FAIL  {:<20} {}", check, detail);
+    }
+}
+
+/// The first line printed by `program version_arg`.
+fn version(path: &Path, version_arg: &str) -> Option<String> {
+    let output = Command::new(path).arg(version_arg).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?.trim();
+    (output.status.success() && !line.is_empty()).then(|| line.to_owned())
+}
+
+/// The program of a command like `okular {path}`.
+fn program_of(command: &str) -> Option<String> {
+    shlex::split(command)?.into_iter().next()
+}
+
+fn check_tools(report: &mut Report, config: &Config) {
+    report.section("Tools");
+    let uses_fd = config.enabled_locations()
+        .any(|(_, loc)| loc.finder == FinderKind::Fd && matches!(loc.mode, LocationMode::Files | LocationMode::Folders | LocationMode::All));
+    let fd = tool("fd", config).get_program().to_string_lossy().into_owned();
+    match find_program(&fd) {
+        Some(path) => report.ok("fd", version(&path, "--version").unwrap_or_else(|| path.to_string_lossy().into_owned())),
+        None if uses_fd => report.warn("fd", "not found, locations are listed with the slower built-in scanner"),
+        None => report.ok("fd", "not needed"),
+    }
+
+    let (picker, version_arg) = match config.picker.kind() {
+        PickerKind::Fzf => (config.picker.command().binary, "--version"),
+        PickerKind::Rofi => ("rofi".to_owned(), "-version"),
+        PickerKind::Wofi => ("wofi".to_owned(), "--version"),
+        PickerKind::Dmenu => ("dmenu".to_owned(), "-v"),
+    };
+    match find_program(&tool(&picker, config).get_program().to_string_lossy()) {
+        Some(path) => report.ok(&picker, version(&path, version_arg).unwrap_or_else(|| path.to_string_lossy().into_owned())),
+        None => report.fail(&picker, "not found, no search can be shown"),
+    }
+
+    let mut finders: Vec<(FinderKind, &str)> = Vec::new();
+    for (name, location) in config.enabled_locations() {
+        if location.finder != FinderKind::Fd && !finders.iter().any(|(f, _)| *f == location.finder) {
+            finders.push((location.finder, name));
+        }
+    }
+    for (finder, location_name) in finders {
+        let program = match finder {
+            FinderKind::Fd => continue,
+            FinderKind::Everything => "es",
+            FinderKind::Mdfind => "mdfind",
+            FinderKind::Locate => "plocate",
+            FinderKind::Git => "git",
+        };
+        match find_program(program) {
+            Some(path) => report.ok(program, path.to_string_lossy()),
+            None => report.fail(program, format!("not found, but used by {}", location_name)),
+        }
+    }
+    if let Some((name, _)) = config.enabled_locations().find(|(_, loc)| loc.remote.is_some()) {
+        match find_program("ssh") {
+            Some(path) => report.ok("ssh", path.to_string_lossy()),
+            None => report.fail("ssh", format!("not found, but used by {}", name)),
+        }
+    }
+}
+
+/// Loads the config, which is only returned if it is valid.
+fn check_config(report: &mut Report) -> Option<Config> {
+    report.section("Config");
+    let path = Config::path();
+    let check = path.file_name().unwrap_or_default().to_string_lossy();
+    if !path.exists() {
+        report.fail(&check, format!("{} does not exist, run bl to create it", path.to_string_lossy()));
+        return None;
+    }
+    let config = match Config::load(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            report.fail(&check, e);
+            return None;
+        },
+    };
+    report.ok(&check, format!("{}, {} locations", path.to_string_lossy(), config.locations.len()));
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for include in config.include.iter().flatten() {
+        if !dir.join(include).exists() {
+            report.warn(include, "included file does not exist");
+        }
+    }
+    if let Err(e) = config.default_location() {
+        report.fail("default_location", e);
+    }
+    Some(config)
+}
+
+fn describe_cache(path: &Path, location: &Location) -> Option<String> {
+    let size = path.metadata().ok()?.len();
+    let age = cache::cache_age(path).unwrap_or_default();
+    let stale = if cache::is_stale(location) { ", stale" } else { "" };
+    let deltas = match cache::delta_paths(path).len() {
+        0 => String::new(),
+        n => format!(" + {} deltas", n),
+    };
+    Some(format!("cache {}{}, {} old{}", file_size(size), deltas, humantime::format_duration(Duration::from_secs(age.as_secs())), stale))
+}
+
+/// A file size like `1.5 MB`.
+pub fn file_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{:.1} KB", b as f64 / 1024.0),
+        b => format!("{} B", b),
+    }
+}
+
+fn check_locations(report: &mut Report, config: &Config) {
+    report.section("Locations");
+    let scanned: Vec<(&String, &Location)> = config.locations.iter()
+        .filter(|(_, loc)| loc.is_enabled() && (!loc.path.is_empty() || loc.remote.is_some()))
+        .collect();
+    let available = finder::available(scanned.iter().map(|(_, loc)| *loc));
+    for (name, location) in config.locations.iter() {
+        if !location.is_enabled() {
+            report.ok(name, "disabled");
+            continue;
+        }
+        match location.mode {
+            LocationMode::Command => match location.command.as_deref().and_then(program_of) {
+                Some(program) if find_program(&program).is_some() => report.ok(name, format!("command {}", program)),
+                Some(program) => report.fail(name, format!("command {} not found", program)),
+                None => report.fail(name, "mode: command needs a command"),
+            },
+            _ if location.list_command.is_some() => match location.list_command.as_deref().and_then(program_of) {
+                Some(program) if find_program(&program).is_some() => report.ok(name, format!("list_command {}", program)),
+                Some(program) => report.fail(name, format!("list_command {} not found", program)),
+                None => report.fail(name, "list_command is empty"),
+            },
+            _ => {
+                let reachable = scanned.iter().position(|(n, _)| *n == name).is_some_and(|i| available[i]);
+                let cache = cache::cache_path(location).map(|path| describe_cache(&path, location));
+                let path = location.remote.as_ref().unwrap_or(&location.path);
+                match (reachable, cache) {
+                    (false, _) if location.optional => report.warn(name, format!("{} is not reachable", path)),
+                    (false, _) => report.fail(name, format!("{} is not reachable", path)),
+                    (true, Some(None)) if location.auto_cache => report.ok(name, format!("{}, no cache yet, created by the first search", path)),
+                    (true, Some(None)) => report.warn(name, format!("{}, no cache yet, run bl --update-cache {}", path, name)),
+                    (true, Some(Some(cache))) => report.ok(name, format!("{}, {}", path, cache)),
+                    (true, None) => report.ok(name, path),
+                }
+            },
+        }
+    }
+}
+
+fn check_openers(report: &mut Report, config: &Config) {
+    report.section("Opener");
+    let opener = open::system_opener();
+    match find_program(opener) {
+        Some(path) => report.ok(opener, path.to_string_lossy()),
+        None => report.fail(opener, "not found, entries cannot be opened"),
+    }
+    let commands = config.open_rules.iter().flatten().map(|(_, command)| command)
+        .chain(config.locations.values().filter_map(|loc| loc.open_command.as_ref()));
+    let mut checked = Vec::new();
+    for program in commands.filter_map(|command| program_of(command)) {
+        if checked.contains(&program) {
+            continue;
+        }
+        match find_program(&program) {
+            Some(path) => report.ok(&program, path.to_string_lossy()),
+            None => report.fail(&program, "not found, but used by an open command"),
+        }
+        checked.push(program);
+    }
+}
+
+/// Prints a report of the tools, config, locations and openers blink needs, failing if any problem is found.
+pub fn run() -> Result<()> {
+    let mut report = Report::default();
+    let config = check_config(&mut report);
+    if let Some(config) = &config {
+        check_tools(&mut report, config);
+        check_locations(&mut report, config);
+        check_openers(&mut report, config);
+    }
+    println!();
+    match report.problems {
+        0 => {
+            println!("No problems found");
+            Ok(())
+        },
+        1 => Err(anyhow::anyhow!("1 problem found")),
+        n => Err(anyhow::anyhow!("{} problems found", n)),
+    }
+}
+
+#[test]
+fn file_sizes() {
+    assert_eq!(file_size(512), "512 B");
+    assert_eq!(file_size(1536), "1.5 KB");
+    assert_eq!(file_size(3 * 1024 * 1024), "3.0 MB");
+}
@@ -0,0 +1,94 @@
+use std::{fmt, io, path::PathBuf, process::{Child, Command}};
+use anyhow::Result;
+
+/// Exit code of errors which are not a [`BlinkError`].
+pub const EXIT_FAILURE: u8 = 1;
+
+/// Failures which scripts calling blink can tell apart by the exit code.
+#[derive(Debug)]
+pub enum BlinkError {
+    /// The picker was closed without choosing anything. Exit code 130, like fzf.
+    Aborted,
+    /// No location matches the given name. Exit code 3.
+    LocationNotFound(String),
+    /// An external program like `fd` or `fzf` is not installed. Exit code 4.
+    ToolMissing(String),
+    /// The config file could not be read. Exit code 5.
+    ConfigInvalid { path: PathBuf, reason: String },
+}
+
+impl BlinkError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            BlinkError::Aborted => 130,
+            BlinkError::LocationNotFound(_) => 3,
+            BlinkError::ToolMissing(_) => 4,
+            BlinkError::ConfigInvalid { .. } => 5,
+        }
+    }
+}
+
+impl fmt::Display for BlinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlinkError::Aborted => write!(f, "Aborted"),
+            BlinkError::LocationNotFound(name) => write!(f, "No location found for {}", name),
+            BlinkError::ToolMissing(tool) => {
+                writeln!(f, "{} is not installed", tool)?;
+                write!(f, "Searched in: ")?;
+                if cfg!(target_os = "windows") {
+                    if let Some(dir) = std::env::current_exe().ok().as_deref().and_then(|e| e.parent()) {
+                        write!(f, "{}, ", dir.to_string_lossy())?;
+                    }
+                }
+                writeln!(f, "$PATH ({})", std::env::var("PATH").unwrap_or_default())?;
+                write!(f, "{}", install_hint(tool))
+            },
+            BlinkError::ConfigInvalid { path, reason } => write!(f, "Invalid config {}: {}", path.to_string_lossy(), reason),
+        }
+    }
+}
+
+impl std::error::Error for BlinkError {}
+
+fn install_hint(tool: &str) -> String {
+    let tool = tool.trim_end_matches(".exe");
+    match tool {
+        "fd" => "Install fd from https://github.com/sharkdp/fd#installation, e.g. `apt install fd-find`, `brew install fd` or `winget install sharkdp.fd`".to_owned(),
+        "fzf" => "Install fzf from https://github.com/junegunn/fzf#installation, e.g. `apt install fzf`, `brew install fzf` or `winget install fzf`".to_owned(),
+        "es" => "Install the Everything command line interface from https://www.voidtools.com/support/everything/command_line_interface/".to_owned(),
+        "plocate" => "Install plocate, e.g. `apt install plocate`, and run `updatedb`".to_owned(),
+        tool => format!("Install {} with your package manager, or set the command in the config", tool),
+    }
+}
+
+/// Exit code for the result of blink.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    error.downcast_ref::<BlinkError>().map(|e| e.exit_code()).unwrap_or(EXIT_FAILURE)
+}
+
+// Extend Command Builder with spawn_tool() function
+pub trait SpawnTool {
+    /// Like `spawn()`, but reports a missing program as [`BlinkError::ToolMissing`].
+    fn spawn_tool(&mut self) -> Result<Child>;
+}
+impl SpawnTool for Command {
+    fn spawn_tool(&mut self) -> Result<Child> {
+        self.spawn().map_err(|e| spawn_error(e, self))
+    }
+}
+
+/// Reports a missing program as [`BlinkError::ToolMissing`].
+pub fn spawn_error(e: io::Error, cmd: &Command) -> anyhow::Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => BlinkError::ToolMissing(cmd.get_program().to_string_lossy().into_owned()).into(),
+        _ => e.into(),
+    }
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(exit_code(&BlinkError::Aborted.into()), 130);
+    assert_eq!(exit_code(&BlinkError::LocationNotFound("x".into()).into()), 3);
+    assert_eq!(exit_code(&anyhow::anyhow!("other")), EXIT_FAILURE);
+}
@@ -0,0 +1,159 @@
+use std::{fs, io::{self, BufRead, BufReader, PipeReader, Write}, path::{Path, PathBuf}, thread, time::SystemTime};
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use log::{debug, error};
+use crate::config::Location;
+use crate::{archive, paths};
+
+/// A pattern of `open_rules` or `filter`: a glob like `*.md`, or a bare extension like `pdf`, which is the same as `*.pdf`.
+/// Patterns containing a slash are matched against the whole path, all others against the file name.
+pub struct Pattern {
+    matcher: GlobMatcher,
+    whole_path: bool,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern> {
+        let glob = match extension(pattern) {
+            Some(ext) => format!("*.{}", ext),
+            None => pattern.to_owned(),
+        };
+        let matcher = GlobBuilder::new(&glob).case_insensitive(true).literal_separator(true).build()?.compile_matcher();
+        Ok(Pattern { matcher, whole_path: glob.contains('/') })
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        match (self.whole_path, path.file_name()) {
+            (true, _) => self.matcher.is_match(path),
+            (false, Some(file_name)) => self.matcher.is_match(file_name),
+            (false, None) => false,
+        }
+    }
+}
+
+/// The extension of a pattern which is a bare extension like `pdf` or `.pdf`.
+fn extension(pattern: &str) -> Option<&str> {
+    (!pattern.contains(['*', '?', '[', '/'])).then(|| pattern.trim_start_matches('.'))
+}
+
+/// The fd arguments for the `filter` of a location, replacing the `.` pattern which lists everything.
+/// fd only takes one glob, so other filters are only applied by [`filter_entries`].
+pub fn fd_args(filter: Option<&[String]>) -> Vec<String> {
+    match filter {
+        Some(patterns) if !patterns.is_empty() && patterns.iter().all(|p| extension(p).is_some()) =>
+            std::iter::once(".".to_owned())
+                .chain(patterns.iter().flat_map(|p| ["--extension".to_owned(), extension(p).unwrap().to_owned()]))
+                .collect(),
+        Some([pattern]) if !pattern.contains('/') => vec!["--glob".to_owned(), pattern.clone()],
+        _ => vec![".".to_owned()],
+    }
+}
+
+/// The modification time given to `--newer` or `--older`: a duration before now like `7d`, or a date like `2023-01-01`.
+pub fn parse_time(time: &str) -> Result<SystemTime> {
+    if let Ok(duration) = humantime::parse_duration(time) {
+        return SystemTime::now().checked_sub(duration).ok_or_else(|| anyhow::anyhow!("Invalid time {}, it is too long ago", time));
+    }
+    let time = if time.len() == 10 { format!("{} 00:00:00", time) } else { time.to_owned() };
+    humantime::parse_rfc3339_weak(&time)
+        .map_err(|_| anyhow::anyhow!("Invalid time {}, expected a duration like 7d or a date like 2023-01-01", time))
+}
+
+/// The fd arguments for the `newer` and `older` limits of a location.
+pub fn fd_time_args(location: &Location) -> Vec<String> {
+    let newer = location.newer.iter().flat_map(|t| ["--changed-within".to_owned(), t.clone()]);
+    let older = location.older.iter().flat_map(|t| ["--changed-before".to_owned(), t.clone()]);
+    newer.chain(older).collect()
+}
+
+/// What an entry of the location has to match: one of the `filter` patterns, and the `newer` and `older` limits.
+struct Filter {
+    patterns: Option<Vec<Pattern>>,
+    newer: Option<SystemTime>,
+    older: Option<SystemTime>,
+    root: PathBuf,
+}
+
+impl Filter {
+    fn new(location: &Location) -> Result<Option<Filter>> {
+        let (newer, older) = match location.remote {
+            // fd applies the limits on the remote, whose entries cannot be checked here
+            Some(_) => (None, None),
+            None => (location.newer.as_deref(), location.older.as_deref()),
+        };
+        if location.filter.is_none() && newer.is_none() && older.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Filter {
+            patterns: location.filter.as_ref().map(|f| f.iter().map(|p| Pattern::new(p)).collect()).transpose()?,
+            newer: newer.map(parse_time).transpose()?,
+            older: older.map(parse_time).transpose()?,
+            root: PathBuf::from(&location.path),
+        }))
+    }
+
+    fn is_match(&self, entry: &Path) -> bool {
+        if let Some(patterns) = &self.patterns {
+            if !patterns.iter().any(|p| p.is_match(entry)) {
+                return false;
+            }
+        }
+        if self.newer.is_none() && self.older.is_none() {
+            return true;
+        }
+        // entries which cannot be read, like those of unreachable cache locations, are dropped
+        // members of archives count as modified with their archive
+        let file = archive::split_member(entry).map_or_else(|| entry.to_owned(), |(archive, _)| archive);
+        let Ok(modified) = fs::metadata(self.root.join(file)).and_then(|m| m.modified()) else {
+            return false;
+        };
+        self.newer.is_none_or(|newer| modified >= newer) && self.older.is_none_or(|older| modified < older)
+    }
+}
+
+/// Drops the entries which do not match the `filter`, `newer` and `older` settings of the location, in a background thread.
+pub fn filter_entries(list: PipeReader, location: &Location) -> Result<PipeReader> {
+    let Some(filter) = Filter::new(location)? else {
+        return Ok(list);
+    };
+    let (reader, mut writer) = io::pipe()?;
+    thread::spawn(move || {
+        let write = || -> io::Result<()> {
+            for entry in BufReader::new(list).split(b'\n') {
+                let entry = entry?;
+                if filter.is_match(&paths::from_bytes(entry.clone())) {
+                    writer.write_all(&entry)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            Ok(())
+        };
+        match write() {
+            // the picker closing the pipe early is expected
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => debug!("Stopped filtering entries: {}", e),
+            Err(e) => error!("Failed to filter entries: {}", e),
+            Ok(()) => (),
+        }
+    });
+    Ok(reader)
+}
+
+#[test]
+fn fd_args_for_filters() {
+    let args = |patterns: &[&str]| fd_args(Some(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()));
+    assert_eq!(args(&["pdf", ".docx"]), [".", "--extension", "pdf", "--extension", "docx"]);
+    assert_eq!(args(&["*.md"]), ["--glob", "*.md"]);
+    assert_eq!(args(&["*.md", "pdf"]), ["."]);
+    assert!(Pattern::new("notes/*.md").unwrap().is_match(Path::new("notes/todo.md")));
+    assert!(!Pattern::new("pdf").unwrap().is_match(Path::new("pdf/readme.txt")));
+}
+
+#[test]
+fn times_of_newer_and_older() {
+    let week_ago = parse_time("7d").unwrap();
+    let expected = SystemTime::now() - std::time::Duration::from_secs(7 * 24 * 60 * 60);
+    assert!(expected.duration_since(week_ago).unwrap() < std::time::Duration::from_secs(5));
+    assert_eq!(parse_time("2023-01-01").unwrap(), SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1672531200));
+    assert!(parse_time("400000000000y").is_err());
+    assert!(parse_time("last week").is_err());
+}
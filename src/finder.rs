@@ -0,0 +1,728 @@
+use std::{collections::HashMap, fs::{self, File}, io::{self, BufRead, BufReader, PipeReader, Read, Write}, path::{Path, PathBuf}, process::{Child, Command, ExitStatus, Stdio}, sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex}, thread, time::{Duration, Instant, SystemTime}};
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use crate::config::{Config, FinderKind, Location, LocationMode};
+use crate::index::{self, normalize, Separator};
+use crate::{archive, cache, crypt, error, filter, paths, run, tool, WithFunction};
+
+/// A source of the entries of a location, selected with the `finder` of the location.
+pub trait Finder {
+    /// Lists the entries as one native path per line, relative to the location.
+    /// The watchdog tells whether the list was cut off by the `scan_timeout`.
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)>;
+
+    /// The paths of all entries, relative to the location.
+    fn entries(&self, location: &Location, config: &Config) -> Result<Box<dyn Iterator<Item = io::Result<PathBuf>>>> {
+        let (list, _) = self.list(location, config)?;
+        Ok(Box::new(BufReader::new(list).split(b'\n').map(|entry| entry.map(paths::from_bytes))))
+    }
+}
+
+/// The finder for reading the entries of a location: its cache file if it has one, else its [`scanner`].
+pub fn for_location(location: &Location) -> Box<dyn Finder> {
+    match cache::cache_path(location) {
+        Some(path) if location.auto_cache && !path.exists() => Box::new(CachingScanner(path)),
+        Some(path) => Box::new(CacheFile(path)),
+        None => scanner(location),
+    }
+}
+
+/// The finder for scanning a location: the `command` of `mode: command` locations or the `list_command`,
+/// else its `finder`.
+pub fn scanner(location: &Location) -> Box<dyn Finder> {
+    match location.mode {
+        LocationMode::Command => Box::new(Provider),
+        LocationMode::Group => Box::new(Members),
+        LocationMode::Stdin => Box::new(StdinList),
+        _ if location.list_command.is_some() => Box::new(Provider),
+        _ if location.remote.is_some() => Box::new(Remote),
+        LocationMode::All => Box::new(FoldersAndFiles),
+        LocationMode::Files if location.index_archives => Box::new(Archives(Box::new(location.finder))),
+        _ => Box::new(location.finder),
+    }
+}
+
+impl Finder for FinderKind {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let mut finder = finder_cmd(*self, location, config);
+        let (list, watchdog): (Box<dyn Read + Send>, _) = match finder.cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn() {
+            Ok(mut child) => (Box::new(child.stdout.take().unwrap()), Watchdog::start(child, location)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound && *self == FinderKind::Fd => {
+                info!("fd not found, listing {} with the built-in scanner", location.path);
+                (Box::new(walk(location)?), Watchdog::none())
+            },
+            Err(e) => return Err(error::spawn_error(e, &finder.cmd)),
+        };
+        Ok((normalize(list, finder.separator, finder.strip_prefix.as_deref(), finder.only_type)?, watchdog))
+    }
+}
+
+/// Lists the folders of a `mode: all` location, each ending with a `/`, and then its files.
+pub struct FoldersAndFiles;
+
+impl Finder for FoldersAndFiles {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let folders = Location { mode: LocationMode::Folders, ..location.clone() };
+        let files = Location { mode: LocationMode::Files, ..location.clone() };
+        // both scans run at once, the files are read once all folders are listed
+        let (folder_list, folder_watchdog) = scanner(&folders).list(&folders, config)?;
+        let (file_list, file_watchdog) = scanner(&files).list(&files, config)?;
+        let (reader, mut writer) = io::pipe()?;
+        thread::spawn(move || {
+            let write = || -> io::Result<()> {
+                for entry in BufReader::new(folder_list).split(b'\n') {
+                    writer.write_all(&entry?)?;
+                    writer.write_all(b"/\n")?;
+                }
+                io::copy(&mut BufReader::new(file_list), &mut writer)?;
+                Ok(())
+            };
+            if let Err(e) = write() {
+                // the picker closing the pipe early is expected
+                debug!("Stopped listing files and folders: {}", e);
+            }
+        });
+        Ok((reader, Watchdog::either(folder_watchdog, file_watchdog)))
+    }
+}
+
+/// Lists the members of the zip and tar archives found by another finder after each archive,
+/// for locations with `index_archives`.
+pub struct Archives(pub Box<dyn Finder>);
+
+impl Finder for Archives {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let (list, watchdog) = self.0.list(location, config)?;
+        if !cfg!(feature = "archives") {
+            warn!("index_archives is set, but blink was built without the archives feature");
+            return Ok((list, watchdog));
+        }
+        Ok((archive::expand_entries(list, location)?, watchdog))
+    }
+}
+
+/// Runs the `command` of a `mode: command` location, or the `list_command` of another one,
+/// which prints the entries from any source.
+pub struct Provider;
+
+impl Finder for Provider {
+    fn list(&self, location: &Location, _config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let command = location.list_command.as_deref().or(location.command.as_deref())
+            .ok_or_else(|| anyhow::anyhow!("Location {} has mode command, but no command", location.path))?;
+        let mut words = shlex::split(command)
+            .ok_or_else(|| anyhow::anyhow!("Invalid command: {}", command))?
+            .into_iter();
+        let mut cmd = Command::new(words.next().ok_or_else(|| anyhow::anyhow!("Empty command"))?);
+        cmd
+            .args(words)
+            .with(|b| if !location.path.is_empty() { b.current_dir(&location.path); })
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .with(|b| debug!("Executing: {:?}", b));
+        let mut child = cmd.spawn().map_err(|e| error::spawn_error(e, &cmd))?;
+        let list = child.stdout.take().unwrap();
+        Ok((normalize(list, location.separator.unwrap_or(Separator::Any), None, None)?, Watchdog::start(child, location)))
+    }
+}
+
+/// Reads the entries piped into blink, separated by newlines or NUL bytes.
+pub struct StdinList;
+
+impl Finder for StdinList {
+    fn list(&self, _location: &Location, _config: &Config) -> Result<(PipeReader, Watchdog)> {
+        Ok((normalize(io::stdin(), Separator::Any, None, None)?, Watchdog::none()))
+    }
+}
+
+/// Lists the locations of a group one after another, each entry prefixed with the name of its location.
+/// Unavailable locations are skipped.
+pub struct Members;
+
+impl Finder for Members {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let group = location.group.clone().unwrap_or_default();
+        let members: Vec<(String, Location)> = config.members(&group).map(|(n, l)| (n.clone(), l.clone())).collect();
+        let available = available(members.iter().map(|(_, l)| l));
+        let config = config.clone();
+        let (reader, mut writer) = io::pipe()?;
+        thread::spawn(move || {
+            let write = || -> Result<()> {
+                for ((name, member), _) in members.iter().zip(available).filter(|(_, available)| *available) {
+                    for entry in BufReader::new(index::list_location(name, member, &config)?).split(b'\n') {
+                        writer.write_all(name.as_bytes())?;
+                        writer.write_all(b"/")?;
+                        writer.write_all(&entry?)?;
+                        writer.write_all(b"\n")?;
+                    }
+                }
+                Ok(())
+            };
+            match write() {
+                // the picker closing the pipe early is expected
+                Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+                    debug!("Stopped listing group {}: {}", group, e),
+                Err(e) => error!("Failed to list group {}: {}", group, e),
+                Ok(()) => (),
+            }
+        });
+        Ok((reader, Watchdog::none()))
+    }
+}
+
+/// Lists a `remote` location over SSH with fd, or with `find` where fd is not installed,
+/// or reads its `remote_cache`.
+pub struct Remote;
+
+impl Remote {
+    /// The shell command run on the remote.
+    fn script(location: &Location, config: &Config) -> Result<String> {
+        let (_, dir) = location.remote_parts().unwrap_or_default();
+        let cd = format!("cd {}", shlex::try_quote(dir)?);
+        if let Some(cache) = &location.remote_cache {
+            return Ok(format!("{} && cat {}", cd, shlex::try_quote(cache)?));
+        }
+        let fd_args = shlex::try_join(fd_args(location, config).iter().map(String::as_str))?;
+        let find_type = match location.mode {
+            LocationMode::Folders => " -type d",
+            LocationMode::All => "",
+            _ => " -type f",
+        };
+        let find_depth = location.max_depth.map(|depth| format!(" -maxdepth {}", depth)).unwrap_or_default();
+        // the newer and older limits of fd, as minutes before now
+        let minutes = |time: &str| -> Result<u64> {
+            Ok(SystemTime::now().duration_since(filter::parse_time(time)?).unwrap_or_default().as_secs().div_ceil(60))
+        };
+        let mut find_time = String::new();
+        if let Some(newer) = &location.newer {
+            find_time += &format!(" -mmin -{}", minutes(newer)?);
+        }
+        if let Some(older) = &location.older {
+            find_time += &format!(" -mmin +{}", minutes(older)?);
+        }
+        // Debian and Ubuntu install fd as fdfind
+        Ok(format!(
+            "{0} && if command -v fd >/dev/null; then fd {1}; elif command -v fdfind >/dev/null; then fdfind {1}; \
+            else find . -mindepth 1{2}{3}{4} -print0; fi",
+            cd, fd_args, find_depth, find_type, find_time
+        ))
+    }
+}
+
+impl Finder for Remote {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let (host, _) = location.remote_parts()
+            .ok_or_else(|| anyhow::anyhow!("Location {} has no remote", location.path))?;
+        let mut cmd = Command::new("ssh");
+        cmd
+            // a password prompt would hang the picker
+            .arg("-o").arg("BatchMode=yes")
+            .arg(host)
+            .arg(Remote::script(location, config)?)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .with(|b| debug!("Executing: {:?}", b));
+        let mut child = cmd.spawn().map_err(|e| error::spawn_error(e, &cmd))?;
+        let stdout = child.stdout.take().unwrap();
+        let (list, separator): (Box<dyn Read + Send>, _) = match &location.remote_cache {
+            Some(cache) if cache::is_compressed(Path::new(cache)) => (Box::new(zstd::Decoder::new(stdout)?), Separator::Newline),
+            Some(_) => (Box::new(stdout), Separator::Newline),
+            None => (Box::new(stdout), Separator::Null),
+        };
+        if location.remote_cache.as_deref().is_some_and(|cache| cache::is_binary(Path::new(cache))) {
+            return Ok((cache::read_binary(list, None)?, Watchdog::start(child, location)));
+        }
+        // find prints paths starting with ./, which normalizing removes
+        Ok((normalize(list, separator, None, None)?, Watchdog::start(child, location)))
+    }
+}
+
+/// A list of entries written by `--update-cache`, optionally zstd-compressed or in the binary format.
+pub struct CacheFile(pub PathBuf);
+
+impl CacheFile {
+    pub fn read(&self) -> Result<PipeReader> {
+        let path = &self.0;
+        info!("Reading cache file: \"{}\"", path.to_string_lossy());
+        let mut file = File::open(path).map_err(|e| anyhow::anyhow!(
+            "Cannot read cache file {}: {}. Run `bl --update-cache` to create it", path.to_string_lossy(), e))?;
+        let encrypted = crypt::is_encrypted(&mut file)?;
+        let entries = if !encrypted && !cache::is_compressed(path) && !cache::is_binary(path) && cache::can_map(path) && file.metadata()?.len() >= cache::MAP_MIN_SIZE {
+            cache::read_mapped(file)?
+        } else {
+            let file: Box<dyn Read + Send> = match encrypted {
+                true => crypt::decrypt_from(file)?,
+                false => Box::new(file),
+            };
+            let input: Box<dyn Read + Send> = if cache::is_compressed(path) {
+                Box::new(zstd::Decoder::new(file)?)
+            } else {
+                file
+            };
+            match cache::is_binary(path) {
+                // the entries were normalized when they were written, a wrong key is no damage
+                true => cache::read_binary(input, (!encrypted).then_some(path.as_path()))?,
+                false => normalize(input, Separator::Newline, None, None)?,
+            }
+        };
+        let deltas = cache::delta_paths(path);
+        match deltas.is_empty() {
+            true => Ok(entries),
+            false => cache::apply_deltas(entries, &deltas),
+        }
+    }
+}
+
+impl Finder for CacheFile {
+    fn list(&self, _location: &Location, _config: &Config) -> Result<(PipeReader, Watchdog)> {
+        Ok((self.read()?, Watchdog::none()))
+    }
+}
+
+/// Scans an `auto_cache` location and writes the entries to its missing cache file as well.
+/// The cache file is only written if the scan completes before blink exits.
+pub struct CachingScanner(pub PathBuf);
+
+impl Finder for CachingScanner {
+    fn list(&self, location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+        let Some(lock) = cache::CacheLock::try_acquire(&self.0)? else {
+            debug!("Cache file \"{}\" is being created by another blink", self.0.to_string_lossy());
+            return scanner(location).list(location, config);
+        };
+        if self.0.exists() {
+            // created by another blink meanwhile
+            return CacheFile(self.0.clone()).list(location, config);
+        }
+        info!("Creating cache file: \"{}\"", self.0.to_string_lossy());
+        let (list, watchdog) = scanner(location).list(location, config)?;
+        let mut cache = cache::CacheWriter::create(&self.0, location, lock)?;
+        let (reader, writer) = io::pipe()?;
+        let scan = watchdog.clone();
+        let path = self.0.clone();
+        thread::spawn(move || {
+            let mut writer = Some(writer);
+            let write = || -> io::Result<()> {
+                for entry in BufReader::new(list).split(b'\n') {
+                    let entry = entry?;
+                    cache.write_entry(&entry)?;
+                    // the scan goes on for the cache file after the picker closed
+                    if writer.as_mut().is_some_and(|w| w.write_all(&entry).and_then(|_| w.write_all(b"\n")).is_err()) {
+                        writer = None;
+                    }
+                }
+                Ok(())
+            };
+            // the list only ends once the cache file is in place, as blink may exit right after
+            match write() {
+                Ok(()) if !scan.timed_out() && !scan.failed() => match cache.finish() {
+                    Ok(()) => info!("Cache file \"{}\" created", path.to_string_lossy()),
+                    Err(e) => warn!("Cannot create cache file {}: {}", path.to_string_lossy(), e),
+                },
+                _ => cache.discard(),
+            }
+        });
+        Ok((reader, watchdog))
+    }
+}
+
+/// A command listing all entries of a location, and how to read its output.
+pub struct FinderCmd {
+    pub cmd: Command,
+    pub separator: Separator,
+    /// Set if the command prints absolute paths, which are made relative to this prefix.
+    pub strip_prefix: Option<PathBuf>,
+    /// Set if the command cannot filter by type itself, so entries are checked afterwards.
+    pub only_type: Option<LocationMode>,
+}
+
+pub fn finder_cmd(kind: FinderKind, location: &Location, config: &Config) -> FinderCmd {
+    match kind {
+        FinderKind::Fd => FinderCmd {
+            cmd: fd_cmd(location, config),
+            separator: Separator::Null,
+            strip_prefix: None,
+            only_type: None,
+        },
+        FinderKind::Everything => FinderCmd {
+            cmd: everything_cmd(location),
+            separator: Separator::Newline,
+            strip_prefix: Some(PathBuf::from(&location.path)),
+            only_type: None,
+        },
+        FinderKind::Mdfind => FinderCmd {
+            cmd: mdfind_cmd(location),
+            separator: Separator::Null,
+            strip_prefix: Some(PathBuf::from(&location.path)),
+            only_type: None,
+        },
+        FinderKind::Locate => FinderCmd {
+            cmd: locate_cmd(location),
+            separator: Separator::Null,
+            strip_prefix: Some(PathBuf::from(&location.path)),
+            only_type: Some(location.mode),
+        },
+        FinderKind::Git => FinderCmd {
+            cmd: git_cmd(location),
+            separator: Separator::Null,
+            strip_prefix: None,
+            only_type: None,
+        },
+    }
+}
+
+/// The arguments of fd for listing the location, without the path.
+fn fd_args(location: &Location, config: &Config) -> Vec<String> {
+    let mut args = filter::fd_args(location.filter.as_deref());
+    args.push("--print0".to_owned());
+    match location.mode {
+        LocationMode::Files | LocationMode::Command | LocationMode::Group | LocationMode::Stdin => args.extend(["--type".to_owned(), "f".to_owned()]),
+        LocationMode::Folders => args.extend(["--type".to_owned(), "d".to_owned()]),
+        LocationMode::All => (),
+    }
+    args.extend(location.max_depth.map(|depth| format!("--max-depth={}", depth)));
+    args.extend(filter::fd_time_args(location));
+    if location.hidden {
+        args.push("--hidden".to_owned());
+    }
+    if location.no_ignore {
+        args.push("--no-ignore".to_owned());
+    }
+    args.extend(config.fd_flags.iter().flatten().cloned());
+    args
+}
+
+fn fd_cmd(location: &Location, config: &Config) -> Command {
+    let mut cmd = tool("fd", config);
+    cmd
+        .args(fd_args(location, config))
+        .current_dir(&location.path)
+        .with(|b| debug!("Executing: {:?}", b));
+    cmd
+}
+
+/// Queries the voidtools Everything index using its command line interface `es.exe`.
+fn everything_cmd(location: &Location) -> Command {
+    let mut cmd = run("es");
+    cmd
+        .arg("-path").arg(&location.path)
+        .arg(match location.mode {
+            LocationMode::Files | LocationMode::Command | LocationMode::Group | LocationMode::Stdin | LocationMode::All => "/a-d",
+            LocationMode::Folders => "/ad",
+        })
+        .with(|b| debug!("Executing: {:?}", b));
+    cmd
+}
+
+/// Queries the Spotlight index on macOS, scoped to the location path.
+fn mdfind_cmd(location: &Location) -> Command {
+    let mut cmd = Command::new("mdfind");
+    cmd
+        .arg("-0")
+        .arg("-onlyin").arg(&location.path)
+        .arg(match location.mode {
+            LocationMode::Files | LocationMode::Command | LocationMode::Group | LocationMode::Stdin | LocationMode::All => "kMDItemContentType != public.folder",
+            LocationMode::Folders => "kMDItemContentType == public.folder",
+        })
+        .with(|b| debug!("Executing: {:?}", b));
+    cmd
+}
+
+/// Reads the `plocate` database, limited to entries below the location path.
+fn locate_cmd(location: &Location) -> Command {
+    let root = location.path.trim_end_matches('/');
+    let mut cmd = Command::new("plocate");
+    cmd
+        .arg("--null")
+        .arg("--regex").arg(format!("^{}/", regex::escape(root)))
+        .with(|b| debug!("Executing: {:?}", b));
+    cmd
+}
+
+/// Lists tracked and untracked, non-ignored files of a git repository.
+/// Git does not track folders, so folder mode lists the folders of the `HEAD` tree.
+fn git_cmd(location: &Location) -> Command {
+    let mut cmd = Command::new("git");
+    match location.mode {
+        LocationMode::Files | LocationMode::Command | LocationMode::Group | LocationMode::Stdin | LocationMode::All => cmd
+            .arg("ls-files").arg("-z")
+            .arg("--cached").arg("--others")
+            .with(|b| if !location.no_ignore { b.arg("--exclude-standard"); }),
+        LocationMode::Folders => cmd
+            .arg("ls-tree").arg("-z")
+            .arg("-d").arg("-r").arg("--name-only").arg("HEAD"),
+    };
+    cmd
+        .current_dir(&location.path)
+        .with(|b| debug!("Executing: {:?}", b));
+    cmd
+}
+
+/// Kills a finder which is still running after the `scan_timeout` of its location,
+/// e.g. `fd` hanging on an unreachable network share, and notes whether it failed.
+#[derive(Clone)]
+pub struct Watchdog(Vec<Arc<Scan>>);
+
+/// What the watchdog found out about one finder.
+#[derive(Default)]
+struct Scan {
+    timed_out: AtomicBool,
+    /// Whether the finder did not fail, see [`exit_failed`], once it exited
+    success: Mutex<Option<bool>>,
+    exited: Condvar,
+}
+
+/// How long [`Watchdog::failed`] waits for a finder to exit after its output ended.
+const EXIT_WAIT: Duration = Duration::from_secs(1);
+
+/// Whether a finder which exited with `status` failed, so its list cannot replace the cache file.
+/// Finders exit with an error for unreadable subfolders as well, so this only counts if the location cannot
+/// be reached anymore, e.g. a network share or the ssh connection of a remote location went away.
+/// Finders stopped by a signal, like SIGPIPE after the picker closed the list, have not failed either.
+fn exit_failed(status: Option<ExitStatus>, location: &Location) -> bool {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt;
+    match status {
+        Some(status) if status.success() => false,
+        #[cfg(unix)]
+        Some(status) if status.signal().is_some() => false,
+        // ssh exits with 255 for connection errors
+        Some(status) if location.remote.is_some() => status.code() == Some(255),
+        _ if location.remote.is_some() => true,
+        // commands without a path only have their exit status to go by
+        _ if location.path.is_empty() => true,
+        _ => !Path::new(&location.path).is_dir(),
+    }
+}
+
+impl Watchdog {
+    pub fn start(mut child: Child, location: &Location) -> Watchdog {
+        let scan = Arc::new(Scan::default());
+        let (location, state) = (location.clone(), scan.clone());
+        thread::spawn(move || {
+            let status = match location.scan_timeout {
+                Some(timeout) => Self::wait_or_kill(&mut child, timeout, &state, &location.path),
+                None => child.wait().ok(),
+            };
+            let timed_out = state.timed_out.load(Ordering::SeqCst);
+            let success = timed_out || !exit_failed(status, &location);
+            if !success {
+                warn!("Scanning {} failed, the list is incomplete", location.path);
+            } else if !timed_out && status.is_some_and(|status| !status.success()) {
+                debug!("The finder of {} exited with {:?}", location.path, status);
+            }
+            *state.success.lock().unwrap() = Some(success);
+            state.exited.notify_all();
+        });
+        Watchdog(vec![scan])
+    }
+
+    /// Waits for the finder to exit, or kills it after the timeout. Returns how it exited, unless it was killed.
+    fn wait_or_kill(child: &mut Child, timeout: Duration, scan: &Scan, path: &str) -> Option<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(None) => thread::sleep(Duration::from_millis(100)),
+                Ok(Some(status)) => return Some(status),
+                Err(_) => return None,
+            }
+        }
+        // set before killing, so the flag is visible once the list ends
+        scan.timed_out.store(true, Ordering::SeqCst);
+        warn!("Scanning {} timed out after {:?}, the list is incomplete", path, timeout);
+        if let Err(e) = child.kill().and_then(|_| child.wait()) {
+            debug!("Failed to stop the finder for {}: {}", path, e);
+        }
+        None
+    }
+
+    /// A watchdog for scans which cannot time out, like the built-in scanner.
+    pub fn none() -> Watchdog {
+        Watchdog(Vec::new())
+    }
+
+    /// Watches the finders of both watchdogs, timed out if either one did.
+    pub fn either(mut first: Watchdog, second: Watchdog) -> Watchdog {
+        first.0.extend(second.0);
+        first
+    }
+
+    /// Whether the finder was killed. Only final once its output has ended.
+    pub fn timed_out(&self) -> bool {
+        self.0.iter().any(|scan| scan.timed_out.load(Ordering::SeqCst))
+    }
+
+    /// Whether the finder failed, as the path became unreachable during the scan, see [`exit_failed`].
+    /// Only called once its output has ended, as it waits for the finder to exit.
+    pub fn failed(&self) -> bool {
+        self.0.iter().any(|scan| {
+            let success = scan.exited.wait_timeout_while(scan.success.lock().unwrap(), EXIT_WAIT, |s| s.is_none()).unwrap().0;
+            *success == Some(false) && !scan.timed_out.load(Ordering::SeqCst)
+        })
+    }
+}
+
+/// How long reaching the path of a location may take, e.g. on a network share, before it counts as unavailable.
+const AVAILABILITY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A check whether a path can be reached, with its result once it is known.
+#[derive(Default)]
+struct Availability {
+    reachable: Mutex<Option<bool>>,
+    checked: Condvar,
+}
+
+/// The last check of each path. A check stuck on a hung network share is waited for again instead of
+/// starting another one, so at most one thread per path is blocked.
+static AVAILABILITY: Mutex<Option<HashMap<PathBuf, Arc<Availability>>>> = Mutex::new(None);
+
+fn check_availability(path: PathBuf) -> Arc<Availability> {
+    let mut checks = AVAILABILITY.lock().unwrap();
+    let checks = checks.get_or_insert_default();
+    if let Some(check) = checks.get(&path).filter(|check| check.reachable.lock().unwrap().is_none()) {
+        return check.clone();
+    }
+    let check = Arc::new(Availability::default());
+    checks.insert(path.clone(), check.clone());
+    let state = check.clone();
+    thread::spawn(move || {
+        *state.reachable.lock().unwrap() = Some(path.is_dir());
+        state.checked.notify_all();
+    });
+    check
+}
+
+/// Checks in parallel whether the paths of the locations can be reached.
+pub fn available<'a>(locations: impl IntoIterator<Item = &'a Location>) -> Vec<bool> {
+    let checks: Vec<Option<Arc<Availability>>> = locations.into_iter()
+        .map(|location| {
+            // the `recent` pseudo-location has no path, and remote locations are only reached by listing them
+            (!location.path.is_empty() && location.remote.is_none()).then(|| check_availability(PathBuf::from(&location.path)))
+        })
+        .collect();
+    let deadline = Instant::now() + AVAILABILITY_TIMEOUT;
+    checks.into_iter()
+        .map(|check| match check {
+            Some(check) => {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                let reachable = check.checked.wait_timeout_while(check.reachable.lock().unwrap(), timeout, |r| r.is_none()).unwrap().0;
+                reachable.unwrap_or(false)
+            },
+            None => true,
+        })
+        .collect()
+}
+
+pub fn is_available(location: &Location) -> bool {
+    available([location])[0]
+}
+
+/// Lists the entries of the location like `fd`, without hidden entries unless the location sets `hidden`,
+/// for systems where fd is not installed.
+/// Writes NUL-separated paths relative to the location.
+pub fn walk(location: &Location) -> io::Result<PipeReader> {
+    let (reader, mut writer) = io::pipe()?;
+    let root = PathBuf::from(&location.path);
+    let (mode, max_depth, hidden) = (location.mode, location.max_depth, location.hidden);
+    thread::spawn(move || {
+        if let Err(e) = walk_dir(&root, Path::new(""), mode, max_depth, hidden, &mut writer) {
+            // the reader closing the pipe early is expected
+            debug!("Stopped listing {}: {}", root.to_string_lossy(), e);
+        }
+    });
+    Ok(reader)
+}
+
+/// Lists the entries of `dir` and, up to `max_depth` levels below it, of its subfolders.
+fn walk_dir(root: &Path, dir: &Path, mode: LocationMode, max_depth: Option<usize>, hidden: bool, out: &mut impl Write) -> io::Result<()> {
+    if max_depth == Some(0) {
+        return Ok(());
+    }
+    let Ok(entries) = fs::read_dir(root.join(dir)) else {
+        debug!("Skipping unreadable folder {}", root.join(dir).to_string_lossy());
+        return Ok(());
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !hidden && entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = dir.join(entry.file_name());
+        // like fd, symlinks are listed but not followed
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() == (mode == LocationMode::Folders) {
+            out.write_all(&paths::to_bytes(&path))?;
+            out.write_all(b"\0")?;
+        }
+        if file_type.is_dir() {
+            walk_dir(root, &path, mode, max_depth.map(|depth| depth - 1), hidden, out)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn cache_file_entries() {
+    let path = std::env::temp_dir().join(format!("blink-test-{}.txt", std::process::id()));
+    fs::write(&path, "./a.txt\r\nsub/b.pdf\n\n").unwrap();
+    let entries: Vec<PathBuf> = CacheFile(path.clone()).entries(&Location::default(), &Config::default()).unwrap()
+        .collect::<io::Result<_>>().unwrap();
+    fs::remove_file(path).unwrap();
+    assert_eq!(entries, [PathBuf::from("a.txt"), PathBuf::from("sub/b.pdf")]);
+}
+
+#[test]
+fn walk_stops_at_max_depth() {
+    let root = std::env::temp_dir().join(format!("blink-walk-{}", std::process::id()));
+    fs::create_dir_all(root.join("a/b/c")).unwrap();
+    let location = Location { path: root.to_string_lossy().into_owned(), mode: LocationMode::Folders, max_depth: Some(2), ..Default::default() };
+    let mut entries = Vec::new();
+    walk(&location).unwrap().read_to_end(&mut entries).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+    let entries: Vec<PathBuf> = entries.split(|&b| b == b'\0').filter(|e| !e.is_empty()).map(|e| paths::from_bytes(e.to_vec())).collect();
+    assert_eq!(entries, [PathBuf::from("a"), Path::new("a").join("b")]);
+}
+
+#[cfg(unix)]
+#[test]
+fn walk_keeps_non_utf8_names() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let root = std::env::temp_dir().join(format!("blink-walk-bytes-{}", std::process::id()));
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join(name), "").unwrap();
+    let location = Location { path: root.to_string_lossy().into_owned(), mode: LocationMode::Files, ..Default::default() };
+    let mut entries = Vec::new();
+    walk(&location).unwrap().read_to_end(&mut entries).unwrap();
+    fs::remove_dir_all(&root).unwrap();
+    assert_eq!(entries, b"caf\xe9.txt\0");
+}
+
+#[test]
+fn remote_find_limits() {
+    let location = Location {
+        remote: Some("nas:projects".to_owned()),
+        mode: LocationMode::Files,
+        max_depth: Some(2),
+        newer: Some("1h".to_owned()),
+        ..Default::default()
+    };
+    let script = Remote::script(&location, &Config::default()).unwrap();
+    assert!(script.ends_with("find . -mindepth 1 -maxdepth 2 -type f -mmin -60 -print0; fi"), "{}", script);
+}
+
+#[cfg(unix)]
+#[test]
+fn exit_failures() {
+    use std::os::unix::process::ExitStatusExt;
+    let (error, sigpipe) = (ExitStatus::from_raw(1 << 8), ExitStatus::from_raw(13));
+    let reachable = Location { path: std::env::temp_dir().to_string_lossy().into_owned(), ..Default::default() };
+    let gone = Location { path: "/nonexistent/blink".to_owned(), ..Default::default() };
+    assert!(!exit_failed(Some(error), &reachable) && !exit_failed(Some(sigpipe), &gone));
+    assert!(exit_failed(Some(error), &gone));
+    let remote = Location { remote: Some("nas:projects".to_owned()), ..Default::default() };
+    assert!(!exit_failed(Some(error), &remote) && exit_failed(Some(ExitStatus::from_raw(255 << 8)), &remote));
+}
@@ -0,0 +1,100 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+use anyhow::Result;
+use log::debug;
+use crate::config::Config;
+use crate::location_to_id;
+
+/// The fzf history of searches in the location.
+pub fn path(location_name: &str) -> Result<PathBuf> {
+    Ok(Config::base_dir().join(format!("history-{}.txt", location_to_id(location_name)?)))
+}
+
+/// The fzf history of the location menu.
+pub fn menu_path() -> PathBuf {
+    Config::base_dir().join("history-menu.txt")
+}
+
+/// Keeps only the latest `max_entries` queries of the history file.
+pub fn trim(path: &Path, max_entries: usize) -> Result<()> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+    let queries: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+    if queries.len() > max_entries {
+        debug!("Trimming {} to {} queries", path.to_string_lossy(), max_entries);
+        fs::write(path, queries[queries.len() - max_entries..].iter().map(|q| format!("{}\n", q)).collect::<String>())?;
+    }
+    Ok(())
+}
+
+/// All history files, with the location id or `menu` from their file name.
+pub fn files() -> Result<Vec<(String, PathBuf)>> {
+    let mut files: Vec<(String, PathBuf)> = fs::read_dir(Config::base_dir())?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let id = name.strip_prefix("history-")?.strip_suffix(".txt")?.to_owned();
+            Some((id, entry.path()))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Deletes the history of the location, or all history files.
+pub fn clear(location_name: Option<&str>) -> Result<usize> {
+    let files = match location_name {
+        Some(name) => vec![path(name)?],
+        None => files()?.into_iter().map(|(_, path)| path).collect(),
+    };
+    let mut count = 0;
+    for file in files.iter().filter(|f| f.exists()) {
+        debug!("Deleting {}", file.to_string_lossy());
+        fs::remove_file(file)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes all queries as `id<TAB>query` lines, oldest first within each file.
+pub fn export(out: &mut impl Write) -> Result<()> {
+    for (id, path) in files()? {
+        for query in fs::read_to_string(&path)?.lines().filter(|l| !l.is_empty()) {
+            writeln!(out, "{}\t{}", id, query)?;
+        }
+    }
+    Ok(())
+}
+
+/// The queries of the location, latest first and without repeats.
+pub fn queries(location_name: &str) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path(location_name)?).unwrap_or_default();
+    let queries: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+    Ok(dedupe_queries(&queries).into_iter().rev().map(str::to_owned).collect())
+}
+
+/// Removes repeated queries from all history files, keeping the latest of each. Returns the number of removed lines.
+pub fn dedupe() -> Result<usize> {
+    let mut removed = 0;
+    for (_, path) in files()? {
+        let content = fs::read_to_string(&path)?;
+        let queries: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+        let unique = dedupe_queries(&queries);
+        removed += queries.len() - unique.len();
+        fs::write(&path, unique.iter().map(|q| format!("{}\n", q)).collect::<String>())?;
+    }
+    Ok(removed)
+}
+
+/// fzf appends the latest query at the end, so the last occurrence of each query is kept.
+fn dedupe_queries<'a>(queries: &[&'a str]) -> Vec<&'a str> {
+    let mut seen = std::collections::HashSet::new();
+    let mut unique: Vec<&str> = queries.iter().rev().filter(|q| seen.insert(**q)).copied().collect();
+    unique.reverse();
+    unique
+}
+
+#[test]
+fn dedupe_keeps_latest() {
+    assert_eq!(dedupe_queries(&["a", "b", "a", "c", "b"]), ["a", "c", "b"]);
+}
@@ -0,0 +1,87 @@
+use std::{env, process::Command};
+use anyhow::Result;
+use log::debug;
+use blink_search::config::{Config, PickerKind};
+use blink_search::WithFunction;
+
+#[cfg(feature = "hotkey")]
+const DEFAULT_HOTKEY: &str = "ctrl+alt+b";
+
+/// Starts blink in a new window, with the location or the location menu.
+/// fzf-like pickers need a terminal, the others open their own window.
+#[cfg_attr(not(any(feature = "hotkey", feature = "tray")), allow(dead_code))]
+pub fn launch(config: &Config, location: Option<&str>) -> Result<()> {
+    let exe = env::current_exe()?;
+    let mut cmd = if config.picker.kind() != PickerKind::Fzf {
+        Command::new(&exe)
+    } else if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        // the first quoted argument of start is the window title
+        cmd.arg("/C").arg("start").arg("").arg(&exe);
+        cmd
+    } else {
+        let mut cmd = Command::new(env::var("TERMINAL").unwrap_or_else(|_| "x-terminal-emulator".to_owned()));
+        cmd.arg("-e").arg(&exe);
+        cmd
+    };
+    match location {
+        Some(location) => cmd.arg(location),
+        None => cmd.arg("--menu"),
+    };
+    cmd
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    Ok(())
+}
+
+/// Registers the `hotkey` of the config and calls [`launch`] whenever it is pressed. Runs until killed.
+#[cfg(feature = "hotkey")]
+pub fn run(config: Config) -> Result<()> {
+    use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+
+    if cfg!(target_os = "macos") {
+        return Err(anyhow::anyhow!("--hotkey is not supported on macOS. Bind `bl --menu` in the system settings instead"));
+    }
+    let key = config.hotkey.as_deref().unwrap_or(DEFAULT_HOTKEY);
+    let hotkey: HotKey = key.parse().map_err(|e| anyhow::anyhow!("Invalid hotkey {}: {}", key, e))?;
+    let manager = GlobalHotKeyManager::new()?;
+    manager.register(hotkey)?;
+    println!("Press {} to open blink", key);
+
+    GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+        if event.id == hotkey.id() && event.state == HotKeyState::Pressed {
+            if let Err(e) = launch(&config, None) {
+                log::error!("Failed to launch blink: {}", e);
+            }
+        }
+    }));
+
+    wait_for_events();
+    Ok(())
+}
+
+/// Hotkeys and tray events are reported through the message loop of the thread which registered them.
+#[cfg(all(any(feature = "hotkey", feature = "tray"), target_os = "windows"))]
+pub fn wait_for_events() {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{DispatchMessageW, GetMessageW, TranslateMessage, MSG};
+    unsafe {
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+}
+
+/// X11 and D-Bus events are handled on threads of global-hotkey and ksni.
+#[cfg(all(any(feature = "hotkey", feature = "tray"), not(target_os = "windows")))]
+pub fn wait_for_events() {
+    loop {
+        std::thread::park();
+    }
+}
+
+#[cfg(not(feature = "hotkey"))]
+pub fn run(_config: Config) -> Result<()> {
+    Err(anyhow::anyhow!("blink was built without hotkey support. Rebuild with `--features hotkey`"))
+}
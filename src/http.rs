@@ -0,0 +1,98 @@
+use anyhow::Result;
+use blink_search::config::Config;
+
+#[cfg(feature = "http")]
+const DEFAULT_PORT: u16 = 7575;
+
+/// Answers JSON queries on localhost until killed:
+/// `GET /locations`, `GET /search?loc=nas&q=report&limit=20` and `POST /open` with `{"location": "nas", "entry": "..."}`.
+#[cfg(feature = "http")]
+pub fn serve(config: Config) -> Result<()> {
+    use log::{debug, warn};
+    use serde_json::{json, Value};
+    use tiny_http::{Header, Method, Request, Response, Server};
+    use blink_search::search;
+    use blink_search::index::entry_path;
+
+    let port = config.http_port.unwrap_or(DEFAULT_PORT);
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| anyhow::anyhow!("Failed to listen on port {}: {}", port, e))?;
+    println!("Listening on http://127.0.0.1:{}", port);
+
+    let search = |params: &[(String, String)]| -> Result<Value> {
+        let param = |name: &str| params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+        let location_name = param("loc").ok_or_else(|| anyhow::anyhow!("Missing parameter loc"))?;
+        let location = config.locations.get(location_name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown location {}", location_name))?;
+        let query = param("q").unwrap_or("");
+        let limit = param("limit").map(|l| l.parse()).transpose()?.unwrap_or(20);
+        Ok(Value::Array(search::search(location_name, location, &config, query, limit)?.into_iter()
+            .map(|(entry, score)| json!({ "entry": entry.to_string_lossy(), "path": entry_path(location, &entry, &config).to_string_lossy(), "score": score }))
+            .collect()))
+    };
+
+    let open = |body: &str| -> Result<Value> {
+        let request: Value = serde_json::from_str(body)?;
+        let location_name = request["location"].as_str().ok_or_else(|| anyhow::anyhow!("Missing location"))?;
+        let entry = request["entry"].as_str().ok_or_else(|| anyhow::anyhow!("Missing entry"))?;
+        let path = crate::rpc::open_entry(location_name, entry, &config)?;
+        Ok(json!({ "opened": path.to_string_lossy() }))
+    };
+
+    // web pages resolving their own host name to 127.0.0.1 (DNS rebinding) send their name as Host
+    let hosts = [format!("127.0.0.1:{}", port), format!("localhost:{}", port)];
+    let handle = |mut request: Request| -> Result<()> {
+        debug!("HTTP {} {}", request.method(), request.url());
+        let host = request.headers().iter().find(|h| h.field.equiv("Host")).map(|h| h.value.as_str().to_owned());
+        if !host.is_some_and(|host| hosts.contains(&host)) {
+            request.respond(Response::empty(403))?;
+            return Ok(());
+        }
+        let url = request.url().to_owned();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+        let is_json = request.headers().iter()
+            .any(|h| h.field.equiv("Content-Type") && h.value.as_str().starts_with("application/json"));
+        let result = match (request.method(), path) {
+            (Method::Get, "/locations") => Ok(Value::Array(config.enabled_locations()
+                .map(|(name, loc)| json!({ "name": name, "path": loc.path, "mode": loc.mode.to_string() }))
+                .collect())),
+            (Method::Get, "/search") => search(&params),
+            // requiring JSON keeps other websites from opening files, as browsers preflight such requests
+            (Method::Post, "/open") if is_json => {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body)?;
+                open(&body)
+            },
+            (Method::Post, "/open") => Err(anyhow::anyhow!("Expected Content-Type: application/json")),
+            _ => {
+                request.respond(Response::empty(404))?;
+                return Ok(());
+            },
+        };
+        let (status, body) = match result {
+            Ok(value) => (200, value),
+            Err(e) => (400, json!({ "error": e.to_string() })),
+        };
+        let mut response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+        if let Some(origin) = &config.http_allow_origin {
+            response.add_header(Header::from_bytes("Access-Control-Allow-Origin", origin.as_bytes()).unwrap());
+        }
+        request.respond(response)?;
+        Ok(())
+    };
+
+    for request in server.incoming_requests() {
+        // a broken request only fails itself
+        if let Err(e) = handle(request) {
+            warn!("Cannot answer HTTP request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+pub fn serve(_config: Config) -> Result<()> {
+    Err(anyhow::anyhow!("blink was built without the HTTP API. Rebuild with `--features http`"))
+}
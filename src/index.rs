@@ -0,0 +1,267 @@
+use std::{collections::HashSet, io::{self, BufRead, BufReader, PipeReader, Read, Write}, path::{Path, PathBuf}, thread};
+use anyhow::Result;
+use clap::{Parser, ValueEnum};
+use log::{debug, error};
+use serde::{Deserialize, Serialize};
+use crate::config::{Config, Location, LocationMode, Source};
+use crate::finder::{self, CacheFile, Watchdog};
+use crate::{cache, filter, open, paths, script, search};
+
+/// The entries of one location, read from its cache file or by scanning it.
+pub struct Index<'a> {
+    pub name: &'a str,
+    pub location: &'a Location,
+    config: &'a Config,
+}
+
+impl<'a> Index<'a> {
+    pub fn new(name: &'a str, location: &'a Location, config: &'a Config) -> Index<'a> {
+        Index { name, location, config }
+    }
+
+    /// The paths of all entries, relative to the location. Entries are read while the location is scanned.
+    pub fn entries(&self) -> Result<impl Iterator<Item = io::Result<PathBuf>>> {
+        let list = read_location(self.name, self.location, self.config)?;
+        Ok(BufReader::new(list).split(b'\n').map(|entry| entry.map(paths::from_bytes)))
+    }
+
+    /// The best `limit` entries for the query, with their scores.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<(PathBuf, i64)>> {
+        search::search(self.name, self.location, self.config, query, limit)
+    }
+
+    /// Scans the location and replaces its cache file. Returns the number of entries written.
+    pub fn update_cache(&self) -> Result<usize> {
+        cache::update(self.location, self.config)
+    }
+}
+
+#[derive(Parser, Debug, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// How the entries given to [`normalize`] are separated.
+pub enum Separator {
+    Null,
+    Newline,
+    /// Either of both, for commands which may print both
+    Any,
+}
+
+
+/// Normalizes the entries of a finder or cache file in a background thread.
+pub fn normalize(file_names: impl Read + Send + 'static, sep: Separator, strip_prefix: Option<&Path>, only_type: Option<LocationMode>) -> Result<PipeReader> {
+    let (reader, mut writer) = io::pipe()?;
+    let strip_prefix = strip_prefix.map(Path::to_owned);
+    thread::spawn(move || {
+        match normalize_entries(BufReader::new(file_names), &mut writer, sep, strip_prefix.as_deref(), only_type) {
+            // the picker closing the pipe early is expected
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => debug!("Stopped normalizing entries: {}", e),
+            Err(e) => error!("Failed to read entries: {}", e),
+            Ok(()) => (),
+        }
+    });
+    Ok(reader)
+}
+
+/// Writes the entries, separated by `sep`, as one native path per line. `strip_prefix` makes absolute paths relative,
+/// `only_type` drops entries which are not files or folders respectively.
+pub fn normalize_entries(input: impl BufRead, out: &mut impl Write, sep: Separator, strip_prefix: Option<&Path>, only_type: Option<LocationMode>) -> io::Result<()> {
+    let sep = match sep {
+        Separator::Null => (b'\0', b'\0'),
+        Separator::Newline => (b'\n', b'\n'),
+        Separator::Any => (b'\0', b'\n'),
+    };
+    for line in input.split2(sep.0, sep.1) {
+        let Some(path) = paths::clean_entry(&line?) else {
+            continue;
+        };
+        let path = path.as_path();
+        match only_type {
+            Some(LocationMode::Files) if !path.is_file() => continue,
+            Some(LocationMode::Folders) if !path.is_dir() => continue,
+            _ => (),
+        }
+        let path = match strip_prefix {
+            Some(prefix) => path.strip_prefix(prefix).unwrap_or(path),
+            None => path,
+        };
+        out.write_all(&paths::to_bytes(path))?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Lists the entries of a cache file.
+pub fn read_location_from_cache(path: PathBuf) -> Result<PipeReader> {
+    CacheFile(path).read()
+}
+
+/// Lists the entries of a location by scanning it with its finder.
+pub fn read_location_live(location: &Location, config: &Config) -> Result<(PipeReader, Watchdog)> {
+    finder::scanner(location).list(location, config)
+}
+
+/// Lists the entries of a location from its cache file, or by scanning it, after the `transform` script.
+pub fn read_location(location_name: &str, location: &Location, config: &Config) -> Result<PipeReader> {
+    script::transform_entries(list_location(location_name, location, config)?, config)
+}
+
+/// Like [`read_location`], as shown in the picker, where the entries of `source: merged` locations which
+/// are only in the cache start with [`cache::CACHE_ONLY_MARKER`].
+pub fn read_location_marked(location_name: &str, location: &Location, config: &Config) -> Result<PipeReader> {
+    script::transform_entries(list_entries(location_name, location, config, true)?, config)
+}
+
+/// Lists the entries of a location from its cache file, or by scanning it. A stale cache file is refreshed in the background.
+pub fn list_location(location_name: &str, location: &Location, config: &Config) -> Result<PipeReader> {
+    list_entries(location_name, location, config, false)
+}
+
+fn list_entries(location_name: &str, location: &Location, config: &Config, mark_cache_only: bool) -> Result<PipeReader> {
+    if cache::is_stale(location) && !location.revalidate && !cache::is_updating(location) {
+        cache::refresh_in_background(location_name, location, config)?;
+    }
+    if location.source == Source::Merged {
+        if let Some(cache_path) = cache::cache_path(location).filter(|p| p.exists()) {
+            let (live, watchdog) = read_location_live(location, config)?;
+            let cached = filter::filter_entries(read_location_from_cache(cache_path)?, location)?;
+            return merge_entries(filter::filter_entries(live, location)?, watchdog, cached, mark_cache_only);
+        }
+    }
+    filter::filter_entries(finder::for_location(location).list(location, config)?.0, location)
+}
+
+/// Lists the live entries, then those of the cache which were not found, in a background thread.
+/// Duplicates are left out. Unless the scan timed out or failed, the entries only in the cache are marked if `mark` is set.
+fn merge_entries(live: PipeReader, watchdog: Watchdog, cached: PipeReader, mark: bool) -> Result<PipeReader> {
+    let (reader, mut writer) = io::pipe()?;
+    thread::spawn(move || {
+        let mut seen = HashSet::new();
+        let write = || -> io::Result<()> {
+            for entry in BufReader::new(live).split(b'\n') {
+                let entry = entry?;
+                if !seen.contains(&entry) {
+                    writer.write_all(&entry)?;
+                    writer.write_all(b"\n")?;
+                    seen.insert(entry);
+                }
+            }
+            // a scan which timed out or failed says nothing about the entries it did not reach
+            let marker = match mark && !watchdog.timed_out() && !watchdog.failed() {
+                true => cache::CACHE_ONLY_MARKER.as_bytes(),
+                false => b"",
+            };
+            for entry in BufReader::new(cached).split(b'\n') {
+                let entry = entry?;
+                if seen.insert(entry.clone()) {
+                    writer.write_all(marker)?;
+                    writer.write_all(&entry)?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = write() {
+            // the picker closing the pipe early is expected
+            debug!("Stopped merging the cache: {}", e);
+        }
+    });
+    Ok(reader)
+}
+
+/// The full path of an entry of the location. Entries of a group start with the name of their location,
+/// others are relative to the location, or absolute like pinned entries of groups.
+pub fn entry_path(location: &Location, entry: &Path, config: &Config) -> PathBuf {
+    if location.mode == LocationMode::Group {
+        let mut components = entry.components();
+        let member = components.next().and_then(|c| config.locations.get(&*c.as_os_str().to_string_lossy()));
+        if let Some(member) = member.filter(|m| m.group == location.group) {
+            return location_entry_path(member, components.as_path());
+        }
+    }
+    location_entry_path(location, entry)
+}
+
+/// The entry below the path of the location. Entries of remote locations without a local path are `sftp://` URLs.
+fn location_entry_path(location: &Location, entry: &Path) -> PathBuf {
+    if let Some((host, dir)) = location.remote_parts().filter(|_| location.path.is_empty()) {
+        if let Some(uri) = open::sftp_uri(host, dir, entry) {
+            return PathBuf::from(uri);
+        }
+    }
+    Path::new(&location.path).join(entry)
+}
+
+// Extend BufRead with split2() function
+trait Split2Ext: BufRead + Sized {
+    /// Like `split()`, but ends the entries at either delimiter.
+    fn split2(self, delim1: u8, delim2: u8) -> Split2<Self>;
+}
+impl<R: BufRead> Split2Ext for R {
+    fn split2(self, delim1: u8, delim2: u8) -> Split2<Self> {
+        Split2 { reader: self, delim: (delim1, delim2) }
+    }
+}
+struct Split2<R: BufRead> {
+    reader: R,
+    delim: (u8, u8),
+}
+impl<R: BufRead> Iterator for Split2<R> {
+    type Item = io::Result<Vec<u8>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = Vec::new();
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(s) => s,
+                Err(e) => return Some(Err(e)),
+            };
+            if available.is_empty() {
+                return (!buf.is_empty()).then_some(Ok(buf));
+            }
+            match memchr::memchr2(self.delim.0, self.delim.1, available) {
+                Some(i) => {
+                    buf.extend_from_slice(&available[..i]);
+                    self.reader.consume(i + 1);
+                    return Some(Ok(buf));
+                },
+                None => {
+                    let used = available.len();
+                    buf.extend_from_slice(available);
+                    self.reader.consume(used);
+                },
+            }
+        }
+    }
+}
+
+#[test]
+fn split_at_either_delimiter() {
+    let entries: Vec<Vec<u8>> = io::Cursor::new(b"a\0b\n\nc").split2(b'\0', b'\n').collect::<io::Result<_>>().unwrap();
+    assert_eq!(entries, [b"a".to_vec(), b"b".to_vec(), b"".to_vec(), b"c".to_vec()]);
+}
+
+#[test]
+fn group_entries_resolve_to_their_location() {
+    let config = crate::config::ConfigFormat::Yaml.parse("
+locations:
+  movies: { path: /media/movies, mode: files, group: media }
+  music: { path: /media/music, mode: files, group: media }
+  docs: { path: /docs, mode: files }
+").unwrap();
+    let media = Location::of_group("media");
+    assert_eq!(config.groups(), ["media"]);
+    assert_eq!(entry_path(&media, Path::new("music/a/b.mp3"), &config), Path::new("/media/music/a/b.mp3"));
+    assert_eq!(entry_path(&media, Path::new("docs/x"), &config), Path::new("docs/x"));
+    assert_eq!(entry_path(&media, Path::new("/media/movies/c.mkv"), &config), Path::new("/media/movies/c.mkv"));
+}
+
+#[test]
+fn merged_entries_mark_those_only_cached() {
+    let pipe = |content: &'static [u8]| {
+        let (reader, mut writer) = io::pipe().unwrap();
+        thread::spawn(move || writer.write_all(content));
+        reader
+    };
+    let mut merged = String::new();
+    merge_entries(pipe(b"a\nb\na\n"), Watchdog::none(), pipe(b"c\nb\n"), true).unwrap().read_to_string(&mut merged).unwrap();
+    assert_eq!(merged, format!("a\nb\n{}c\n", cache::CACHE_ONLY_MARKER));
+}
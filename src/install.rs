@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+/// Downloads fd and fzf for this system into [`blink_search::tools_dir`], where they are preferred over those in `$PATH`.
+#[cfg(feature = "install-deps")]
+pub fn install_deps(fd_version: Option<&str>, fzf_version: Option<&str>) -> Result<()> {
+    use log::{info, warn};
+    use blink_search::{assets, tools_dir};
+
+    let target = assets::host_target()?;
+    let dir = tools_dir();
+    std::fs::create_dir_all(&dir)?;
+    let fd = assets::fd_asset(fd_version.unwrap_or(assets::DEFAULT_FD_VERSION), &target)?;
+    let fzf = assets::fzf_asset(fzf_version.unwrap_or(assets::DEFAULT_FZF_VERSION), &target)?;
+    for asset in [fd, fzf] {
+        println!("Downloading {}...", asset.url);
+        info!("Downloading {}", asset.url);
+        let bytes = reqwest::blocking::get(&asset.url)?.error_for_status()?.bytes()?;
+        assets::verify_checksum(&asset.url, &bytes)?;
+        if !assets::is_pinned(&asset.url) {
+            warn!("No SHA-256 pinned for {}, installing it unchecked", asset.url);
+            eprintln!("Warning: no SHA-256 pinned for {}, it was not checked. Compare its SHA-256 {} with the checksums of the release",
+                asset.url, assets::sha256(&bytes));
+        }
+        // extracted next to the old version first, so a failed download keeps it
+        let path = dir.join(&asset.file_name);
+        let tmp_path = path.with_extension("download");
+        assets::extract(&asset, bytes.to_vec(), &tmp_path)?;
+        // renaming onto an existing file fails on Windows
+        let _ = std::fs::remove_file(&path);
+        std::fs::rename(&tmp_path, &path)?;
+        println!("Installed {}", path.to_string_lossy());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "install-deps"))]
+pub fn install_deps(_fd_version: Option<&str>, _fzf_version: Option<&str>) -> Result<()> {
+    Err(anyhow::anyhow!("blink was built without install-deps. Rebuild with `--features install-deps`, or install fd and fzf with your package manager"))
+}
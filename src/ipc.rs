@@ -0,0 +1,151 @@
+use std::{io::{self, BufRead, BufReader, Write}, process::{Child, Command, ExitStatus}, sync::Mutex, thread};
+use anyhow::Result;
+use interprocess::local_socket::{prelude::*, ListenerOptions, Name, Stream};
+use log::debug;
+use blink_search::WithFunction;
+
+/// What a second invocation of blink asks the running one to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Only come to the foreground.
+    Focus,
+    /// Show the location menu.
+    Menu,
+    /// Switch to the location.
+    Open(String),
+}
+
+impl Request {
+    fn to_line(&self) -> String {
+        match self {
+            Request::Focus => "focus".to_owned(),
+            Request::Menu => "menu".to_owned(),
+            Request::Open(location) => format!("open {}", location),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Request> {
+        match line.trim_end_matches(['\r', '\n']) {
+            "focus" => Some(Request::Focus),
+            "menu" => Some(Request::Menu),
+            line => line.strip_prefix("open ").map(|l| Request::Open(l.to_owned())),
+        }
+    }
+}
+
+/// The socket of the running search.
+const SERVICE: &str = "blink-search";
+
+/// The picker of the current search, killed when another location is requested.
+static PICKER: Mutex<Option<Child>> = Mutex::new(None);
+static REQUEST: Mutex<Option<Request>> = Mutex::new(None);
+
+/// The local socket of the service for the current user. On Windows, it is the named pipe `\\.\pipe\{service}-{user}`.
+#[cfg(windows)]
+pub fn socket_name(service: &str) -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericNamespaced;
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    format!("{}-{}", service, user).to_ns_name::<GenericNamespaced>()
+}
+
+/// The local socket of the service for the current user, `{service}.sock` in a folder only the user can open:
+/// `$XDG_RUNTIME_DIR`, or else the `run` folder next to the config file.
+#[cfg(not(windows))]
+pub fn socket_name(service: &str) -> io::Result<Name<'static>> {
+    use std::{os::unix::fs::{DirBuilderExt, PermissionsExt}, path::PathBuf};
+    use interprocess::local_socket::GenericFilePath;
+    let dir = match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let dir = blink_search::config::Config::base_dir().join("run");
+            std::fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+            dir
+        },
+    };
+    dir.join(format!("{}.sock", service)).to_fs_name::<GenericFilePath>()
+}
+
+/// Hands the request over to an already running search. Returns false if there is none.
+pub fn forward(request: &Request) -> bool {
+    let Ok(mut stream) = socket_name(SERVICE).and_then(Stream::connect) else {
+        return false;
+    };
+    // allow the running instance to take the focus from this one
+    #[cfg(target_os = "windows")]
+    unsafe { windows_sys::Win32::UI::WindowsAndMessaging::AllowSetForegroundWindow(windows_sys::Win32::UI::WindowsAndMessaging::ASFW_ANY); }
+    debug!("Forwarding {:?} to the running instance", request);
+    writeln!(stream, "{}", request.to_line()).is_ok()
+}
+
+/// Listens for requests of other invocations while this one shows the search.
+pub fn serve() -> Result<()> {
+    let listener = ListenerOptions::new().name(socket_name(SERVICE)?).try_overwrite(true).create_sync()?;
+    thread::spawn(move || {
+        for conn in listener.incoming().filter_map(|c| c.ok()) {
+            let mut line = String::new();
+            if BufReader::new(conn).read_line(&mut line).is_err() {
+                continue;
+            }
+            let Some(request) = Request::parse(&line) else {
+                debug!("Ignoring invalid request: {}", line);
+                continue;
+            };
+            debug!("Received {:?}", request);
+            bring_to_foreground();
+            if request != Request::Focus {
+                *REQUEST.lock().unwrap() = Some(request);
+                close_picker();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Takes a location switch requested by another invocation.
+pub fn take_request() -> Option<Request> {
+    REQUEST.lock().unwrap().take()
+}
+
+/// Registers the picker, so it can be closed when another location is requested.
+/// Read its output before calling [`wait_picker`].
+pub fn track_picker(child: Child) {
+    *PICKER.lock().unwrap() = Some(child);
+}
+
+/// Closes the picker, e.g. when its entries cannot be read.
+pub fn close_picker() {
+    if let Some(picker) = PICKER.lock().unwrap().as_mut() {
+        let _ = picker.kill();
+    }
+}
+
+pub fn wait_picker() -> io::Result<ExitStatus> {
+    let child = PICKER.lock().unwrap().take();
+    child.ok_or_else(|| io::Error::other("no picker is running"))?.wait()
+}
+
+fn bring_to_foreground() {
+    #[cfg(target_os = "windows")]
+    unsafe {
+        let window = windows_sys::Win32::System::Console::GetConsoleWindow();
+        if !window.is_null() {
+            windows_sys::Win32::UI::WindowsAndMessaging::SetForegroundWindow(window);
+        }
+    }
+    // X11 terminals announce their window, which xdotool can activate if installed
+    if let Ok(window) = std::env::var("WINDOWID") {
+        let _ = Command::new("xdotool")
+            .arg("windowactivate").arg(window)
+            .with(|b| debug!("Executing: {:?}", b))
+            .status();
+    }
+}
+
+#[test]
+fn request_lines() {
+    for request in [Request::Focus, Request::Menu, Request::Open("local nas".to_owned())] {
+        assert_eq!(Request::parse(&format!("{}\n", request.to_line())), Some(request));
+    }
+    assert_eq!(Request::parse("shutdown"), None);
+}
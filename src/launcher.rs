@@ -0,0 +1,198 @@
+use std::{io::{Read, Write}, path::{Path, PathBuf}};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use blink_search::config::{Config, Location};
+use blink_search::{cache, paths, search, Opener};
+use blink_search::index::entry_path;
+
+const RESULT_LIMIT: usize = 50;
+
+/// A call of the Flow Launcher (and PowerToys Run) JSON-RPC plugin protocol.
+#[derive(Debug, Deserialize)]
+struct Request {
+    method: String,
+    #[serde(default)]
+    parameters: Vec<Value>,
+}
+
+/// Splits `nas report` into the location `nas` and the query `report`.
+/// Without a matching location name or alias as first word, the default location is searched.
+pub fn split_query<'a>(query: &'a str, config: &'a Config) -> (&'a str, &'a str) {
+    let query = query.trim();
+    let (first, rest) = query.split_once(' ').unwrap_or((query, ""));
+    // the first word may just be a search term, so it never picks a disabled location
+    if let Some(name) = config.find_location(first).filter(|name| config.locations.get(*name).is_some_and(|l| l.is_enabled())) {
+        return (name, rest);
+    }
+    let mut matches = config.enabled_locations().map(|(k, _)| k).filter(|k| k.to_lowercase().starts_with(&first.to_lowercase()));
+    match (matches.next(), matches.next()) {
+        (Some(name), None) if !first.is_empty() => (name, rest),
+        _ => (config.default_location().unwrap_or_default(), query),
+    }
+}
+
+fn query(query: &str, config: &Config) -> Result<Value> {
+    let (location_name, query) = split_query(query, config);
+    let Some(location) = config.locations.get(location_name) else {
+        return Ok(json!({ "result": [] }));
+    };
+    let results: Vec<Value> = search::search(location_name, location, config, query, RESULT_LIMIT)?.into_iter()
+        .map(|(entry, score)| {
+            let path = entry_path(location, &entry, config);
+            let path = path.to_string_lossy();
+            json!({
+                "Title": Path::new(path.as_ref()).file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+                "SubTitle": path,
+                "IcoPath": path,
+                "Score": score,
+                "JsonRPCAction": { "method": "open", "parameters": [path] },
+            })
+        })
+        .collect();
+    Ok(json!({ "result": results }))
+}
+
+/// Output of `--query`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// One full path per line
+    Plain,
+    /// Array of entries with path and score
+    Json,
+    /// Alfred script filter items
+    Alfred,
+    /// Tab-separated fields, one line each
+    Tsv,
+}
+
+/// Prints the best matches of the location for the query.
+pub fn print_search(location_name: &str, location: &Location, config: &Config, query: &str, limit: usize, format: Format) -> Result<()> {
+    let results = search::search(location_name, location, config, query, limit)?;
+    print_matches(results.into_iter().map(|(entry, score)| (entry.to_string_lossy().into_owned(), entry_path(location, &entry, config), score)), format)
+}
+
+/// Prints matches given as entry, full path and score.
+pub fn print_matches(matches: impl Iterator<Item = (String, PathBuf, i64)>, format: Format) -> Result<()> {
+    match format {
+        Format::Plain => {
+            let mut out = std::io::stdout().lock();
+            for (_, path, _) in matches {
+                out.write_all(&paths::to_bytes(&path))?;
+                out.write_all(b"\n")?;
+            }
+        },
+        Format::Json => {
+            let entries: Vec<Value> = matches
+                .map(|(entry, path, score)| json!({ "entry": entry, "path": path.to_string_lossy(), "score": score }))
+                .collect();
+            println!("{}", Value::Array(entries));
+        },
+        Format::Alfred => {
+            let items: Vec<Value> = matches
+                .map(|(entry, path, _)| json!({
+                    "uid": path.to_string_lossy(),
+                    "type": "file",
+                    "title": path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default(),
+                    "subtitle": entry,
+                    "arg": path.to_string_lossy(),
+                    "icon": { "type": "fileicon", "path": path.to_string_lossy() },
+                }))
+                .collect();
+            println!("{}", json!({ "items": items }));
+        },
+        Format::Tsv => {
+            for (entry, path, score) in matches {
+                println!("{}\t{}\t{}", entry, path.to_string_lossy(), score);
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Prints the configured locations. `plain` prints `name (path)`, `json` and `tsv` print the name, path, mode,
+/// cache file, cache age in seconds and whether the location is enabled.
+pub fn print_locations(config: &Config, format: Format) -> Result<()> {
+    let locations = config.locations.iter().map(|(name, loc)| {
+        let cache_file = cache::cache_path(loc);
+        let cache_age = cache_file.as_deref().and_then(cache::cache_age).map(|age| age.as_secs());
+        (name, loc, cache_file, cache_age)
+    });
+    match format {
+        Format::Plain => {
+            for (name, loc, _, _) in locations {
+                println!("{} ({})", name, loc.path);
+            }
+        },
+        Format::Json => {
+            let locations: Vec<Value> = locations
+                .map(|(name, loc, cache_file, cache_age)| json!({
+                    "name": name,
+                    "path": loc.path,
+                    "mode": loc.mode.to_string(),
+                    "cache_file": cache_file.map(|f| f.to_string_lossy().into_owned()),
+                    "cache_age": cache_age,
+                    "enabled": loc.is_enabled(),
+                }))
+                .collect();
+            println!("{}", Value::Array(locations));
+        },
+        Format::Tsv => {
+            for (name, loc, cache_file, cache_age) in locations {
+                println!("{}\t{}\t{}\t{}\t{}\t{}",
+                    name,
+                    loc.path,
+                    loc.mode,
+                    cache_file.map(|f| f.to_string_lossy().into_owned()).unwrap_or_default(),
+                    cache_age.map(|age| age.to_string()).unwrap_or_default(),
+                    loc.is_enabled());
+            }
+        },
+        Format::Alfred => {
+            let items: Vec<Value> = locations
+                .filter(|(_, loc, _, _)| loc.is_enabled())
+                .map(|(name, loc, _, _)| json!({ "uid": name, "title": name, "subtitle": loc.path, "arg": name }))
+                .collect();
+            println!("{}", json!({ "items": items }));
+        },
+    }
+    Ok(())
+}
+
+/// Answers one plugin request, read from `request` or stdin, on stdout.
+pub fn run(request: Option<&str>, config: &Config) -> Result<()> {
+    let request: Request = match request {
+        Some(r) => serde_json::from_str(r)?,
+        None => {
+            let mut r = String::new();
+            std::io::stdin().read_to_string(&mut r)?;
+            serde_json::from_str(&r)?
+        },
+    };
+    let parameter = request.parameters.first().and_then(|p| p.as_str()).unwrap_or_default();
+    match request.method.as_str() {
+        "query" => println!("{}", query(parameter, config)?),
+        "open" => {
+            Opener::new(config).open(Path::new(parameter.trim()))?;
+        },
+        method => return Err(anyhow::anyhow!("Unknown plugin method {}", method)),
+    }
+    Ok(())
+}
+
+#[test]
+fn location_prefix_in_query() {
+    let config = blink_search::config::ConfigFormat::Yaml.parse("
+locations:
+  archive: { path: /archive, mode: files, enabled: false }
+  docs: { path: /docs, mode: files }
+  nas: { path: /nas, mode: folders }
+  nas-backup: { path: /backup, mode: folders, aliases: [bak] }
+").unwrap();
+    assert_eq!(split_query("bak report", &config), ("nas-backup", "report"));
+    assert_eq!(split_query("nas report 2024", &config), ("nas", "report 2024"));
+    assert_eq!(split_query("report", &config), ("docs", "report"));
+    assert_eq!(split_query("", &config), ("docs", ""));
+    assert_eq!(split_query("archive 2019", &config), ("docs", "archive 2019"));
+}
@@ -0,0 +1,114 @@
+//! The indexing and opening of blink locations, used by the `bl` command line tool.
+//!
+//! ```no_run
+//! use blink_search::{Config, Index, Opener};
+//!
+//! let config = Config::new()?;
+//! let (name, location) = config.locations.iter().next().unwrap();
+//! let index = Index::new(name, location, &config);
+//! if let Some((entry, _score)) = index.search("report", 1)?.first() {
+//!     Opener::new(&config).open(&std::path::Path::new(&location.path).join(entry))?;
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+use std::{env, path::{Path, PathBuf}, process::Command};
+use anyhow::Result;
+use regex::Regex;
+
+pub mod archive;
+#[cfg(feature = "install-deps")]
+pub mod assets;
+pub mod cache;
+pub mod config;
+pub mod crypt;
+pub mod error;
+pub mod filter;
+pub mod finder;
+pub mod history;
+pub mod index;
+pub mod open;
+pub mod paths;
+pub mod pins;
+pub mod recent;
+pub mod script;
+pub mod search;
+pub mod wsl;
+
+pub use config::{Config, Location, LocationMode};
+pub use index::Index;
+pub use open::Opener;
+
+/// Where `bl install-deps` puts fd and fzf.
+pub fn tools_dir() -> PathBuf {
+    Config::base_dir().join("bin")
+}
+
+/// A command for the program, with `.exe` appended on Windows. Programs installed by `bl install-deps` are preferred.
+pub fn run(exe: &str) -> Command {
+    let ext = if cfg!(target_os = "windows") { ".exe" } else { "" };
+    let exe = format!("{}{}", exe, ext);
+    let installed = tools_dir().join(&exe);
+    match installed.is_file() {
+        true => Command::new(installed),
+        false => Command::new(exe),
+    }
+}
+
+/// The location name reduced to letters and digits, for file names like the history.
+pub fn location_to_id(location: &str) -> Result<String> {
+    let r = Regex::new(r"[^a-zA-Z0-9]").unwrap().replace_all(location, "");
+    Ok(r.to_lowercase())
+}
+
+/// A command for fd or fzf, from `fd_binary` or `fzf_binary` if set.
+/// Without fd, Debian's and Ubuntu's `fdfind` is used.
+pub fn tool(exe: &str, config: &Config) -> Command {
+    let configured = match exe {
+        "fd" => config.fd_binary.as_ref(),
+        "fzf" => config.fzf_binary.as_ref(),
+        _ => None,
+    };
+    if let Some(binary) = configured {
+        return Command::new(binary);
+    }
+    if exe == "fd" && find_program("fd").is_none() && find_program("fdfind").is_some() {
+        return run("fdfind");
+    }
+    run(exe)
+}
+
+/// Searches the program like [`run`] does: in the [`tools_dir`], next to blink on Windows, then in `$PATH`.
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let program = Path::new(program);
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_owned());
+    }
+    let name = match cfg!(target_os = "windows") && program.extension().is_none() {
+        true => program.with_extension("exe"),
+        false => program.to_owned(),
+    };
+    let exe_dir = env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(Path::to_owned))
+        .filter(|_| cfg!(target_os = "windows"));
+    std::iter::once(tools_dir())
+        .chain(exe_dir)
+        .chain(env::split_paths(&env::var_os("PATH").unwrap_or_default()))
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+// Extend Command Builder with with() function
+pub trait WithFunction {
+    fn with<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self);
+}
+impl WithFunction for Command {
+    fn with<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut Self)
+    {
+        f(self);
+        self
+    }
+}
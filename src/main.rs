@@ -1,192 +1,232 @@
 use anyhow::Result;
-use regex::Regex;
-use config::{Config, Location, LocationMode};
-use memchr;
-use std::{env, ffi::OsString, fs::{File, OpenOptions}, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}, process::{exit, ChildStdout, Command, Stdio}, str::FromStr};
-use clap::{Parser, ValueEnum};
-use log::{info, debug};
+use blink_search::{cache, config, error, filter, finder, history, open, paths, pins, recent, script, wsl};
+use blink_search::index::{entry_path, normalize_entries, read_location_from_cache, read_location_marked, read_location_live, Separator};
+use blink_search::{open::open_folder, Opener, WithFunction};
+use error::BlinkError;
+use config::{Action, Config, Location, LocationMode};
+use std::{collections::HashSet, fs::OpenOptions, io::{self, BufRead, BufReader, Write}, path::{Path, PathBuf}, process::{Command, ExitCode, Stdio}};
+use clap::{builder::PossibleValuesParser, ArgGroup, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use log::{info, debug, error, warn};
 use simplelog::{LevelFilter, WriteLogger};
-use strum;
-mod config;
-
-fn open_folder(path: &str) -> Result<()> {
-    let path = path.trim();
-    debug!("open_folder({})", path);
-
-    let path = path.replace("\\", "/");
-    let path = Regex::new(r"/+").unwrap().replace_all(&path, "/");
-
-    let mut cmd = if cfg!(target_os = "windows") {
-        let mut path = path.to_string();
-        if path.starts_with('/') { path = format!("/{}", path); }
-        path = path.replace("/", "\\");
-        path = path.trim_end_matches('\\').to_owned();
-        let mut cmd = Command::new("explorer");
-        cmd.arg(OsString::from_str(&path)?);
-        cmd
-    } else {
-        let mut cmd = Command::new("xdg-open");
-        cmd.arg(OsString::from_str(&path)?);
-        cmd
-    };
-    cmd
-        .with(|b| debug!("Executing: {:?}", b))
-        .spawn()?;
-    Ok(())
-}
-
-fn location_to_id(location: &str) -> Result<String> {
-    let r = Regex::new(r"[^a-zA-Z0-9]").unwrap().replace_all(location, "");
-    Ok(r.to_lowercase())
-}
-
-fn run(exe: &str) -> Command {
-    let ext = if cfg!(target_os = "windows") { ".exe" } else { "" };
-    Command::new(format!("{}{}", exe, ext))
-}
+use picker::{Choice, Pick};
+mod again;
+mod bench;
+mod daemon;
+#[cfg(all(feature = "dbus", target_os = "linux"))]
+mod dbus;
+mod dmenu;
+mod doctor;
+mod hotkey;
+mod install;
+mod http;
+mod ipc;
+mod launcher;
+mod picker;
+mod prefetch;
+mod preview;
+mod rpc;
+mod schedule;
+mod setup;
+mod stats;
+mod tray;
 
-fn normalize(file_names: Stdio, sep: Separator) -> Result<ChildStdout> {
-    Ok(Command::new(env::current_exe()?)
-        .arg(format!("--normalize-paths={}", sep))
-        .stdin(file_names)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .with(|b| debug!("Executing: {:?}", b))
-        .spawn()?
-        .stdout.unwrap())
+/// Prints the cache first, then all entries of a live scan which were not in the cache.
+fn print_revalidated(location_name: &str, location: &Location, config: &Config) -> Result<()> {
+    let mut seen = HashSet::new();
+    let mut stdout = io::stdout().lock();
+    pins::write_pins(location_name, &mut stdout)?;
+    if let Some(cache_path) = cache::cache_path(location) {
+        for entry in BufReader::new(script::transform_entries(filter::filter_entries(read_location_from_cache(cache_path)?, location)?, config)?).split(b'\n') {
+            let entry = entry?;
+            stdout.write_all(&entry)?;
+            stdout.write_all(b"\n")?;
+            seen.insert(entry);
+        }
+    }
+    stdout.flush()?;
+    // with a scan_timeout, a cut off scan just merges fewer entries into the cached list
+    for entry in BufReader::new(script::transform_entries(filter::filter_entries(read_location_live(location, config)?.0, location)?, config)?).split(b'\n') {
+        let entry = entry?;
+        if !seen.contains(&entry) {
+            stdout.write_all(&entry)?;
+            stdout.write_all(b"\n")?;
+            seen.insert(entry);
+        }
+    }
+    Ok(())
 }
 
-fn read_location_from_cache(path: PathBuf) -> Result<ChildStdout> {
-    info!("Reading cache file: \"{}\"", path.to_string_lossy());
-    let file = match File::open(&path) {
-        Ok(f) => f,
-        Err(_) => {
-            info!("Cache file {} not found. Please check your configuration.", path.to_string_lossy());
-            exit(-1);
-        }
+/// Writes the pins and entries of the location as shown in the search, only the best matches of the query `matching`,
+/// and up to its `max_results`. The fzf header with the number of entries left out is written to `more_file`.
+fn write_results(location_name: &str, location: &Location, config: &Config, matching: Option<&str>, more_file: Option<&Path>, out: &mut impl Write) -> Result<()> {
+    if matching.is_some() {
+        // typing on starts another reload, which stops this one
+        std::thread::sleep(picker::MATCHES_DELAY);
+    }
+    pins::write_pins(location_name, out)?;
+    let max_results = config.max_results(location);
+    let (mut shown, mut more) = (0, 0);
+    let list: Box<dyn io::Read + Send> = match prefetch::take(location_name) {
+        Some(prefetched) => Box::new(prefetched),
+        None => Box::new(read_location_marked(location_name, location, config)?),
     };
-    normalize(file.into(), Separator::Newline)
+    let list: Box<dyn io::Read> = match matching.filter(|query| !query.trim().is_empty()) {
+        Some(query) => Box::new(picker::filter_matches(query, list, config)?),
+        None => list,
+    };
+    for entry in BufReader::new(list).split(b'\n') {
+        let entry = entry?;
+        if max_results == Some(shown) {
+            more += 1;
+            continue;
+        }
+        out.write_all(&entry)?;
+        out.write_all(b"\n")?;
+        shown += 1;
+    }
+    if let Some(path) = more_file.filter(|_| picker::has_dynamic_header(location, config)) {
+        // written before the list ends, which is when fzf shows it
+        picker::write_header(path, location, config, more)?;
+    }
+    Ok(())
 }
 
-fn read_location_cmd(location: &Location, config: &Config) -> Command {
-    let mut cmd = run("fd");
-    cmd
-        .arg(".")
-        .arg("--print0")
-        .arg("--type").arg(match location.mode {
-            LocationMode::Files => "f",
-            LocationMode::Folders => "d",
-        })
-        .args(config.fd_flags.as_ref().unwrap_or(&Vec::new()))
-        .current_dir(&location.path)
-        .with(|b| debug!("Executing: {:?}", b));
-    cmd
+pub enum OpenAction {
+    Open(PathBuf),
+    /// Switch the location, keeping the query.
+    Menu(Option<String>),
+    Actions(PathBuf),
 }
 
-fn read_location_with_fd(location: &Location, config: &Config) -> Result<ChildStdout> {
-    let fd_list = read_location_cmd(location, config)
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .spawn()?
-        .stdout.unwrap();
-    normalize(fd_list.into(), Separator::Null)
+/// The path of a selection printed by the picker.
+pub fn unquote_selection(s: &[u8]) -> PathBuf {
+    let s = match s.trim_ascii() {
+        [b'"', quoted @ .., b'"'] => {
+            let mut unescaped = Vec::with_capacity(quoted.len());
+            let mut bytes = quoted.iter().peekable();
+            while let Some(&b) = bytes.next() {
+                unescaped.push(b);
+                if b == b'\\' && bytes.peek() == Some(&&b'\\') {
+                    bytes.next();
+                }
+            }
+            unescaped
+        },
+        s => s.to_vec(),
+    };
+    paths::from_bytes(pins::strip_marker(&s).to_vec())
 }
 
-enum OpenAction {
-    Open(PathBuf),
-    Menu
+/// The entries written by fzf for `{+f}`, one per line.
+fn read_selection_file(file: &Path) -> Result<Vec<PathBuf>> {
+    // the lines of the file are the entries exactly as listed, without any quoting
+    Ok(std::fs::read(file)?.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| paths::from_bytes(pins::strip_marker(line).to_vec()))
+        .collect())
 }
-fn fzf_open(location_name: &str, location: &Location, config: &Config) -> Result<OpenAction> {
-    let this_exe = env::current_exe()?;
 
-    let fzf_input_list = match &location.cache_file {
-        Some(cache_file) => read_location_from_cache(Path::new(&location.path).join(cache_file))?,
-        None => read_location_with_fd(location, config)?,
+/// Shows the search of a location in the configured picker, starting with the query if given.
+fn open_location(location_name: &str, location: &Location, config: &Config, query: Option<&str>) -> Result<OpenAction> {
+    let (name, loc, cfg) = (location_name.to_owned(), location.clone(), config.clone());
+    let entries: picker::Entries = Box::new(move |mut out| write_results(&name, &loc, &cfg, None, Some(&picker::more_file()), &mut out));
+    let hooks = script::Hooks::load(config)?;
+    let entry = |s: &[u8]| -> Result<PathBuf> {
+        let entry = match &hooks {
+            Some(hooks) => hooks.on_select(&unquote_selection(s))?,
+            None => unquote_selection(s),
+        };
+        Ok(entry_path(location, &entry, config))
     };
+    prefetch::start(Some(location_name), config);
+    let choice = picker::for_config(config).pick(Pick::Search { name: location_name, location, query }, entries);
+    picker::remove_search_files();
+    Ok(match choice? {
+        Choice::Entry(s) => OpenAction::Open(entry(&s)?),
+        Choice::Menu(query) => OpenAction::Menu(query),
+        Choice::EditConfig => OpenAction::Open(Config::path()),
+        Choice::Actions(s) => OpenAction::Actions(entry(&s)?),
+        Choice::Entries(_) => return Err(BlinkError::Aborted.into()),
+    })
+}
 
-    let mut out = run("fzf")
-        .arg("--scheme=path")
-        .arg(format!("--history={}", Config::base_dir().join(format!("history-{}.txt", location_to_id(location_name)?)).to_string_lossy()))
-        .arg("--bind=tab:execute(echo TAB)+abort")
-        .arg(format!("--bind=ctrl-x:execute(\"{}\" --open-path={{}} {})", this_exe.display(), location_name))
-        .arg("--bind=alt-c:execute(echo EDIT_CONFIG)+abort")
-        .args(config.fzf_flags.as_ref().unwrap_or(&Vec::new()))
-
-        .stdin(fzf_input_list)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .with(|b| debug!("Executing: {:?}", b))
-        .spawn()?;
-
-    let reader = std::io::BufReader::new(out.stdout.as_mut().unwrap());
-    let mut action: Option<OpenAction> = None;
-    for line in reader.lines() {
-        debug!("Reading fzf output line: {:?}", line);
-        assert!(action.is_none());
-        action = match line {
-            Ok(ref s) if s == "TAB" => Some(OpenAction::Menu),
-            Ok(ref s) if s == "EDIT_CONFIG" => Some(OpenAction::Open(Config::path())),
-            Ok(s) => {
-                debug!("FZF output: \"{}\"", s);
-                let s = match s.trim() {
-                    s if s.starts_with('"') && s.ends_with('"') => s[1..s.len()-1].replace("\\\\", "\\"),
-                    s => s.to_owned(),
-                };
-                Some(OpenAction::Open(Path::new(&location.path).join(s)))
+/// The entries of the location menu with their location names. Locations which cannot be reached are marked.
+pub fn location_labels(config: &Config) -> Vec<(String, String)> {
+    let available = finder::available(config.enabled_locations().map(|(_, loc)| loc));
+    config.enabled_locations().zip(available)
+        .map(|((name, loc), available)| match (loc.mode, available) {
+            (LocationMode::Group, _) => {
+                let members: Vec<&str> = config.members(name).map(|(n, _)| n.as_str()).collect();
+                (name.to_owned(), format!("{} (group: {})", name, members.join(", ")))
             },
-            Err(e) => panic!("Error reading line: {}", e),
-        }
-    }
-
-    let status = out.wait()?;
-    let ret = status.code().unwrap();
-    match (ret, action) {
-        (130, Some(OpenAction::Menu)) => Ok(OpenAction::Menu),
-        (_, Some(OpenAction::Open(path))) => Ok(OpenAction::Open(path)),
-        _ => return Err(anyhow::anyhow!("fzf exited with code {}", ret)),
-    }
+            (_, true) => (name.to_owned(), format!("{} ({})", name, loc.path)),
+            (_, false) => (name.to_owned(), format!("{} ({}) - unavailable", name, loc.path)),
+        })
+        .collect()
 }
 
-fn fzf_menu(query: Option<&str>, config: &Config) -> Result<String> {
-    let fzf = run("fzf")
-        .arg(format!("--history={}", Config::base_dir().join("history-menu.txt").to_string_lossy()))
-        .arg("--bind").arg("tab:accept")
-        .with(|b| if let Some(q) = query { b.arg(format!("--query={}", q)); })
-        .args(config.fzf_flags.as_ref().unwrap_or(&Vec::new()))
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .with(|b| debug!("Executing: {:?}", b))                
-        .spawn()?;
-
-    for s in config.locations.iter().map(|(name, loc)| format!("{} ({})", name, loc.path)) {
-        writeln!(fzf.stdin.as_ref().unwrap(), "{}", s)?;
+/// Shows the location menu in the configured picker.
+fn choose_location(query: Option<&str>, config: &Config) -> Result<String> {
+    let labels = location_labels(config);
+    let entries = picker::lines(labels.iter().map(|(_, label)| label.clone()).collect());
+    prefetch::start(None, config);
+    match picker::for_config(config).pick(Pick::Locations { query }, entries)? {
+        Choice::Entry(s) => labels.into_iter()
+            .find(|(_, label)| label.as_bytes() == s.trim_ascii())
+            .map(|(name, _)| name)
+            .ok_or_else(|| BlinkError::Aborted.into()),
+        _ => Err(BlinkError::Aborted.into()),
     }
+}
 
-    let out = fzf.wait_with_output()?;
-    let ret = out.status.code().unwrap();
-    let str = String::from_utf8_lossy(&out.stdout);
-    match (ret, str.as_ref()) {
-        (0, s) => {
-            let selection = config.locations.iter()
-                .map(|(name, loc)| (name, format!("{} ({})", name, loc.path)))
-                .find(|(_, text)| text == s.trim())
-                .map(|(name, _)| name.to_owned()).unwrap();
-            Ok(selection)
-        }
-        _ => Err(anyhow::anyhow!("fzf exited with code {}", ret)),
+/// Lets the user choose one of the `action_menu` actions for the selected path.
+fn choose_action(path: &Path, config: &Config) -> Result<Option<Action>> {
+    let actions = config.action_menu();
+    let labels: Vec<String> = actions.iter().map(|a| a.to_string()).collect();
+    let choice = picker::for_config(config).pick(Pick::Actions { path }, picker::lines(labels.clone()));
+    match choice {
+        Ok(Choice::Entry(s)) => Ok(labels.iter()
+            .position(|l| l.as_bytes() == s.trim_ascii())
+            .map(|i| actions[i].clone())),
+        Ok(_) => Ok(None),
+        Err(e) if matches!(e.downcast_ref(), Some(BlinkError::Aborted)) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
 #[derive(Parser)]
-#[command(name="blink search", version, about)]
+#[command(name="blink search", version, about, args_conflicts_with_subcommands = true)]
+#[command(group(ArgGroup::new("listing").args(["query", "list_locations"]).multiple(true)))]
 struct Args {
 
     /// Writes all files or folders to stdout. Useful for automating cache creation.
     #[arg(short, long)]
     create_cache: bool,
 
+    /// Scan the location and write its cache_file.
+    ///
+    /// Use `all` as location to update all locations with a cache_file in parallel.
+    #[arg(short, long)]
+    update_cache: bool,
+
+    /// Register a cron job (Task Scheduler on Windows) running `--update-cache all`, e.g. "0 3 * * *".
+    #[arg(long, value_name = "CRON")]
+    install_schedule: Option<String>,
+
+    /// Remove the job registered by --install-schedule.
+    #[arg(long)]
+    uninstall_schedule: bool,
+
+    /// Number of locations scanned in parallel by `--update-cache all`.
+    #[arg(short, long, default_value_t = 4)]
+    jobs: usize,
+
+    /// Print the cached entries of the location, followed by new entries found by a live scan.
+    #[arg(long, hide = true)]
+    revalidate: bool,
+
     /// List all available locations.
     #[arg(short, long)]
     list_locations: bool,
@@ -195,78 +235,481 @@ struct Args {
     #[arg(short, long)]
     get_config_path: bool,
 
-    /// Directly open path using this query. Useful for scripting.
+    /// Convert the YAML config into TOML (blink.toml). The old file is kept as blink.yml.bak.
+    #[arg(long)]
+    migrate_config: bool,
+
+    /// Start with the location menu instead of the first location.
+    #[arg(long, conflicts_with = "location")]
+    menu: bool,
+
+    /// Wait for the global `hotkey` (default ctrl+alt+b) and open the location menu in a new window when it is pressed.
+    ///
+    /// Requires blink to be built with `--features hotkey`.
+    #[arg(long)]
+    hotkey: bool,
+
+    /// Show a tray icon with the locations, updating all caches and editing the config.
+    ///
+    /// Requires blink to be built with `--features tray`.
+    #[arg(long)]
+    tray: bool,
+
+    /// Answer JSON queries for locations, search results and opening entries on localhost.
+    ///
+    /// Requires blink to be built with `--features http`.
     #[arg(long)]
-    open_path: Option<String>,
+    serve: bool,
+
+    /// Answer JSON-RPC requests on stdin, one per line, for editor plugins running blink as a child process.
+    #[arg(long)]
+    serve_stdio: bool,
+
+    /// Print the best matches of the location for this query, without showing the search.
+    #[arg(long)]
+    query: Option<String>,
+
+    /// Choose several entries opened before, from the recently opened list and the search history, and open them all.
+    #[arg(long)]
+    again: bool,
+
+    /// Output format of --query and --list-locations.
+    #[arg(long, value_enum, default_value = "plain", requires = "listing")]
+    format: launcher::Format,
+
+    /// Maximum number of results printed by --query.
+    #[arg(long, default_value_t = 20, requires = "query")]
+    limit: usize,
+
+    /// Answer a Flow Launcher or PowerToys Run plugin request, given as JSON or read from stdin.
+    ///
+    /// Flow Launcher passes the request as the only argument, which is detected without this flag.
+    #[arg(long, value_name = "JSON", num_args = 0..=1, default_missing_value = "")]
+    flow_launcher: Option<String>,
+
+    /// Directly open path using this query. Useful for scripting.
+    #[arg(long, group = "selection")]
+    open_path: Option<PathBuf>,
+
+    /// Pin a path of the location to the top of its list.
+    #[arg(long, value_name = "PATH")]
+    pin: Option<PathBuf>,
+
+    /// Remove a pinned path of the location.
+    #[arg(long, value_name = "PATH")]
+    unpin: Option<PathBuf>,
+
+    /// Opens the paths of the location listed in this file, one per line. Used by the key bindings.
+    #[arg(long, hide = true, value_name = "FILE", group = "selection")]
+    open_path_file: Option<PathBuf>,
+
+    /// Prints the contents of the folder whose entry is in this file. Used by the fzf preview of folder locations.
+    #[arg(long, hide = true, value_name = "FILE", conflicts_with = "selection")]
+    preview_path_file: Option<PathBuf>,
+
+    /// Used with --open-path(-file) to run the action bound to this key instead of opening the path,
+    /// or with --print-entries to list the entries of the filter bound to it.
+    #[arg(long, hide = true)]
+    run_binding: Option<String>,
+
+    /// Prints this marker for the picker, like `MENU`, followed by --marker-text or the entry in --marker-file.
+    /// Used by the key bindings which leave the search, so the query and entry reach blink unchanged by a shell.
+    #[arg(long, hide = true, value_name = "MARKER")]
+    print_marker: Option<String>,
+
+    /// The text printed after the marker of --print-marker, like the query.
+    #[arg(long, hide = true, requires = "print_marker")]
+    marker_text: Option<String>,
+
+    /// The file whose entry is printed after the marker of --print-marker, written by fzf for `{f}`.
+    #[arg(long, hide = true, value_name = "FILE", requires = "print_marker", conflicts_with = "marker_text")]
+    marker_file: Option<PathBuf>,
 
     /// Normalizes all paths from stdin separated by NULL bytes to a native format separeted by newline. Useful for scripting.
     #[arg(long)]
     normalize_paths: Option<Separator>,
 
+    /// Search this folder like a location, without adding it to the config.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["location", "menu"])]
+    path: Option<PathBuf>,
+
+    /// Search the current folder like --path. Same as `bl .`.
+    #[arg(long, conflicts_with_all = ["location", "menu", "path"])]
+    cwd: bool,
+
+    /// Pick from the lines or NUL-separated paths piped into blink, relative to the current folder.
+    #[arg(long, conflicts_with_all = ["location", "menu", "path", "cwd"])]
+    stdin: bool,
+
+    /// Whether --path and --cwd list files or folders.
+    #[arg(long, value_enum, default_value = "files")]
+    mode: LocationMode,
+
+    /// Only list entries with these extensions, like `pdf,docx`.
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["update_cache", "create_cache"])]
+    ext: Vec<String>,
+
+    /// Only list entries matching this glob, like `*.md`. Patterns with a slash match the whole path.
+    #[arg(long, conflicts_with_all = ["update_cache", "create_cache"])]
+    glob: Vec<String>,
+
+    /// Only list entries modified within this time, like `7d`, or since a date like `2024-01-01`.
+    #[arg(long, value_name = "TIME", conflicts_with_all = ["update_cache", "create_cache"])]
+    newer: Option<String>,
+
+    /// Only list entries modified before this time, like `30d` ago or `2023-01-01`.
+    #[arg(long, value_name = "TIME", conflicts_with_all = ["update_cache", "create_cache"])]
+    older: Option<String>,
+
+    /// Only list entries matching the named filter of the `filters` config, or all for `all`.
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["update_cache", "create_cache"])]
+    filter: Option<String>,
+
+    /// Use the location of the search which started this command, read from its environment. Used by the key bindings.
+    #[arg(long, hide = true, conflicts_with_all = ["location", "path", "cwd", "stdin"])]
+    search_env: bool,
+
+    /// Print the entries of the location as listed in the search. Used by the `filter` key binding.
+    #[arg(long, hide = true)]
+    print_entries: bool,
+
+    /// With --print-entries, list folders instead of files or the other way round, until the search is closed.
+    /// Used by the `toggle-mode` key binding.
+    #[arg(long, hide = true, requires = "print_entries")]
+    toggle_mode: bool,
+
+    /// With --print-entries, scan the location and update its cache file first. Used by the `reload` key binding.
+    #[arg(long, hide = true, requires = "print_entries")]
+    rescan: bool,
+
+    /// With --print-entries, only print the best matches of the query. Used by the search of locations
+    /// with `max_results`.
+    #[arg(long, hide = true, value_name = "QUERY", requires = "print_entries")]
+    matching: Option<String>,
+
+    /// How much is written to blink.log. Overrides the `log` config.
+    #[arg(long, value_enum)]
+    log_level: Option<config::LogLevel>,
+
+    /// Print the selected path instead of opening it, e.g. for `cd "$(bl . --mode folders --print)"`.
+    #[arg(long)]
+    print: bool,
+
     /// Specify the location to search.
     /// 
     /// Accepts shortened if unique.
     /// If not specified, the first location in the config will be used.
     location: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print a shell completion script to stdout.
+    ///
+    /// Location names are read from the config, so regenerate the script after adding locations.
+    Completions {
+        shell: Shell,
+    },
+    /// Manage the fzf history of searched queries.
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Check the tools, config, locations and openers blink needs, and print a report.
+    Doctor,
+    /// Print the entries, cache size and age, history and recently opened count of each location.
+    Stats,
+    /// Time reading the cache, scanning, normalizing and listing the entries of a location, cold and warm.
+    Bench {
+        location: String,
+    },
+    /// Download fd and fzf for this system into the config folder. They are used instead of those in $PATH.
+    InstallDeps {
+        /// fd release to download, e.g. 9.0.0
+        #[arg(long)]
+        fd_version: Option<String>,
+        /// fzf release to download, e.g. 0.46.1
+        #[arg(long)]
+        fzf_version: Option<String>,
+    },
+    /// Keep the entries of `watch: true` locations in memory and answer `bl query`, until killed.
+    ///
+    /// The locations are rescanned, and their cache files updated, every `watch_interval` (10 minutes by default).
+    Daemon,
+    /// Print the best matches of the location for the query, asking `bl daemon` if it is running.
+    ///
+    /// Without a daemon, the location is read like `bl --query` does.
+    Query {
+        location: String,
+        /// Words which all have to be contained in the entries
+        query: Vec<String>,
+        /// Maximum number of results
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        #[arg(long, value_enum, default_value = "plain")]
+        format: launcher::Format,
+    },
 }
 
-#[derive(Parser, Clone, ValueEnum, strum::Display)]
-enum Separator {
-    #[strum(serialize = "null")]
-    Null,
-    #[strum(serialize = "newline")]
-    Newline,
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Delete the history of the location, or all history.
+    Clear {
+        location: Option<String>,
+    },
+    /// Print all queries as `location<TAB>query` lines.
+    Export,
+    /// Remove repeated queries, keeping the latest.
+    Dedupe,
 }
 
 #[test]
 fn verify_cli() {
-    use clap::CommandFactory;
     Args::command().debug_assert()
 }
 
-fn main() -> Result<()> {
-    let config = Config::new()?;
+#[test]
+fn log_rotated_while_writing() {
+    let dir = std::env::temp_dir().join(format!("blink-log-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("blink.log");
+    let mut log = LogFile::open(path.clone(), 10, 2).unwrap();
+    log.write_all(b"0123456789").unwrap();
+    log.write_all(b"abc").unwrap();
+    assert_eq!(std::fs::read_to_string(dir.join("blink.log.1")).unwrap(), "0123456789");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+fn print_completions(shell: Shell, config: &Config) {
+    let names: Vec<String> = config.locations.iter()
+        .flat_map(|(name, loc)| std::iter::once(name).chain(loc.aliases.iter().flatten()))
+        .cloned()
+        .collect();
+    let mut cmd = Args::command()
+        .mut_arg("location", |a| a.value_parser(PossibleValuesParser::new(names)));
+    clap_complete::generate(shell, &mut cmd, "bl", &mut io::stdout());
+}
 
-    let log_name = "blink.log";
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(Config::base_dir().join(log_name))?;
-    WriteLogger::init(LevelFilter::Debug, simplelog::Config::default(), log_file)?;
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // closing the picker is not worth a message
+            if !matches!(e.downcast_ref(), Some(BlinkError::Aborted)) {
+                eprintln!("Error: {:#}", e);
+            }
+            error!("{:#}", e);
+            ExitCode::from(error::exit_code(&e))
+        },
+    }
+}
 
-    debug!("Command line: {:?}", std::env::args().collect::<Vec<String>>());
+/// Renames the log to `blink.log.1`, and older ones to `.2` and so on, once it reaches `max_size`.
+/// `keep` files are kept in total, including the new one.
+fn rotate_log(path: &Path, max_size: u64, keep: usize) -> io::Result<()> {
+    if std::fs::metadata(path).map_or(true, |m| m.len() < max_size) {
+        return Ok(());
+    }
+    let rotated = |i: usize| PathBuf::from(format!("{}.{}", path.to_string_lossy(), i));
+    for i in (1..keep).rev() {
+        let from = if i == 1 { path.to_owned() } else { rotated(i - 1) };
+        if from.exists() {
+            // renaming onto an existing file fails on Windows
+            let _ = std::fs::remove_file(rotated(i));
+            std::fs::rename(from, rotated(i))?;
+        }
+    }
+    if keep <= 1 {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// The log file, rotated whenever it reaches `max_size`, so that long running commands like `bl daemon` rotate it too.
+struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: Option<std::fs::File>,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, max_size: u64, keep: usize) -> io::Result<LogFile> {
+        let mut log = LogFile { path, max_size, keep, file: None, size: 0 };
+        log.reopen()?;
+        Ok(log)
+    }
+
+    fn reopen(&mut self) -> io::Result<&mut std::fs::File> {
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = file.metadata()?.len();
+        Ok(self.file.insert(file))
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size >= self.max_size {
+            // closed first, as open files cannot be renamed on Windows
+            self.file = None;
+            let rotated = rotate_log(&self.path, self.max_size, self.keep);
+            let file = self.reopen()?;
+            if let Err(e) = rotated {
+                writeln!(file, "Cannot rotate the log: {}", e)?;
+                // tried again once as much was written again
+                self.size = 0;
+            }
+        }
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self.reopen()?,
+        };
+        let len = file.write(buf)?;
+        self.size += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().map_or(Ok(()), |file| file.flush())
+    }
+}
+
+/// Runs `--update-cache` for the location in a detached process, which keeps going after the search is closed.
+fn refresh_cache_process(location_name: &str) -> Result<()> {
+    Command::new(std::env::current_exe()?)
+        .arg("--update-cache")
+        .arg(location_name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    Ok(())
+}
 
-    let args = Args::parse();
+fn try_main() -> Result<()> {
+    let mut args = Args::parse();
+    Config::check_base_dir()?;
+    cache::set_background_refresh(refresh_cache_process);
+
+    // the doctor reports an invalid config instead of failing on it
+    if let Some(Commands::Doctor) = args.command {
+        return doctor::run();
+    }
+
+    let mut config = Config::new()?;
+
+    let log = config.log.unwrap_or_default();
+    let log_path = Config::base_dir().join("blink.log");
+    let (max_size, keep) = (log.max_size_mb.unwrap_or(5) * 1024 * 1024, log.keep.unwrap_or(3));
+    // a log which cannot be rotated, e.g. in a read-only folder, is only written on
+    let rotated = rotate_log(&log_path, max_size, keep);
+    let level = args.log_level.or(log.level).map(LevelFilter::from).unwrap_or(LevelFilter::Debug);
+    WriteLogger::init(level, simplelog::Config::default(), LogFile::open(log_path, max_size, keep)?)?;
+    if let Err(e) = rotated {
+        warn!("Cannot rotate the log: {}", e);
+    }
+
+    debug!("Command line: {:?}", std::env::args().collect::<Vec<String>>());
 
     if let Some(separator) = args.normalize_paths {
-        let separator = match separator {
-            Separator::Null => b'\0',
-            Separator::Newline => b'\n',
+        match normalize_entries(io::stdin().lock(), &mut io::stdout().lock(), separator, None, None) {
+            // the reader does not need more entries
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            result => result?,
+        }
+        return Ok(());
+    }
+
+    if let Some(ref marker) = args.print_marker {
+        let mut line = marker.as_bytes().to_vec();
+        let text = match (&args.marker_text, &args.marker_file) {
+            (Some(text), _) => Some(text.as_bytes().to_vec()),
+            (None, Some(file)) => std::fs::read(file)?.split(|&b| b == b'\n').next().map(|entry| entry.strip_suffix(b"\r").unwrap_or(entry).to_vec()),
+            (None, None) => None,
         };
-        for line in BufReader::new(io::stdin()).split(separator) {
-            let s: String = String::from_utf8_lossy(&line?)
-                .trim()
-                .trim_start_matches("./")
-                .trim_start_matches(".\\")
-                .chars().map(|c| if c.is_control() { char::REPLACEMENT_CHARACTER } else { c }).collect();
-            println!("{}", Path::new(&s).to_string_lossy());
+        if let Some(text) = text {
+            line.push(b' ');
+            line.extend(text);
         }
+        line.push(b'\n');
+        io::stdout().lock().write_all(&line)?;
         return Ok(());
     }
 
+    match args.command {
+        Some(Commands::Completions { shell }) => {
+            print_completions(shell, &config);
+            return Ok(());
+        },
+        Some(Commands::History { ref command }) => {
+            match command {
+                HistoryCommand::Clear { location } => {
+                    let location = match location {
+                        Some(name) => Some(config.find_location(name).ok_or_else(|| BlinkError::LocationNotFound(name.clone()))?),
+                        None => None,
+                    };
+                    println!("Deleted {} history files", history::clear(location)?);
+                },
+                HistoryCommand::Export => history::export(&mut io::stdout().lock())?,
+                HistoryCommand::Dedupe => println!("Removed {} repeated queries", history::dedupe()?),
+            }
+            return Ok(());
+        },
+        Some(Commands::Stats) => {
+            stats::print(&config)?;
+            return Ok(());
+        },
+        Some(Commands::Bench { ref location }) => {
+            let name = config.find_location(location).ok_or_else(|| BlinkError::LocationNotFound(location.clone()))?;
+            bench::run(name, &config.locations[name], &config)?;
+            return Ok(());
+        },
+        Some(Commands::InstallDeps { ref fd_version, ref fzf_version }) => {
+            install::install_deps(fd_version.as_deref(), fzf_version.as_deref())?;
+            return Ok(());
+        },
+        Some(Commands::Daemon) => {
+            daemon::run(&config)?;
+            return Ok(());
+        },
+        Some(Commands::Query { ref location, ref query, limit, format }) => {
+            let name = config.find_location(location).ok_or_else(|| BlinkError::LocationNotFound(location.clone()))?;
+            let query = query.join(" ");
+            match daemon::query(&daemon::Query { location: name.to_owned(), query: query.clone(), limit }) {
+                Some(matches) => launcher::print_matches(matches?.into_iter().map(|m| (m.entry, PathBuf::from(m.path), m.score)), format)?,
+                None => {
+                    debug!("No daemon running, reading {}", name);
+                    launcher::print_search(name, &config.locations[name], &config, &query, limit, format)?;
+                },
+            }
+            return Ok(());
+        },
+        Some(Commands::Doctor) | None => (),
+    }
+
     if args.get_config_path {
         println!("{}", Config::path().to_string_lossy());
         return Ok(());
     }
 
+    if args.migrate_config {
+        let path = Config::migrate_to_toml()?;
+        println!("Config migrated to {}", path.to_string_lossy());
+        return Ok(());
+    }
+
     if args.list_locations {
-        for (name, loc) in config.locations.iter() {
-            println!("{} ({})", name, loc.path);
-        }
+        launcher::print_locations(&config, args.format)?;
         return Ok(());
     }
 
-    if config.locations.is_empty() {
+    if config.locations.is_empty() && !setup::run(&mut config)? {
         println!("No locations defined");
         println!("Define locations in {}", Config::path().to_string_lossy());
         println!("Example config with some locations:");
@@ -283,116 +726,316 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(ref schedule) = args.install_schedule {
+        schedule::install(schedule)?;
+        println!("Scheduled cache update: {}", schedule);
+        return Ok(());
+    }
+
+    if args.uninstall_schedule {
+        schedule::uninstall()?;
+        println!("Scheduled cache update removed");
+        return Ok(());
+    }
+
+    if args.hotkey {
+        return hotkey::run(config);
+    }
+
+    if args.tray {
+        return tray::run(config);
+    }
+
+    if args.update_cache && args.location.as_deref() == Some("all") && !config.locations.contains_key("all") {
+        cache::update_all(&config, args.jobs)?;
+        return Ok(());
+    }
+
+    if wsl::is_wsl() {
+        for (name, loc) in config.locations.iter_mut().filter(|(_, loc)| wsl::is_windows_path(&loc.path)) {
+            match wsl::to_linux(&loc.path) {
+                Ok(path) => loc.path = path,
+                Err(e) => info!("Could not translate path of {}: {}", name, e),
+            }
+        }
+    }
+
+    if !config.locations.contains_key(recent::LOCATION_NAME) {
+        config.locations.insert(recent::LOCATION_NAME.to_owned(), recent::location()?);
+    }
+    for group in config.groups() {
+        match config.locations.contains_key(&group) {
+            true => info!("Group {} has the name of a location and cannot be searched", group),
+            false => { config.locations.insert(group.clone(), Location::of_group(&group)); },
+        }
+    }
+
+    if args.serve {
+        return http::serve(config);
+    }
+    if args.serve_stdio {
+        return rpc::serve(&config);
+    }
+
+    if let Some(ref request) = args.flow_launcher {
+        return launcher::run(Some(request.as_str()).filter(|r| !r.is_empty()), &config);
+    }
+    if let Some(request) = args.location.as_deref().filter(|l| l.starts_with("{\"method\"")) {
+        return launcher::run(Some(request), &config);
+    }
+
+    if args.search_env {
+        (args.location, args.path) = picker::search_location();
+        (args.newer, args.older) = picker::search_limits();
+        if let (true, Some(key)) = (args.print_entries, &args.run_binding) {
+            match config.keybindings().remove(key) {
+                Some(Action::Filter { filter }) => args.filter = Some(filter),
+                _ => return Err(anyhow::anyhow!("No filter bound to {}", key)),
+            }
+        }
+        // the filter of a key binding replaces the one of the search
+        if args.filter.is_none() {
+            args.glob = picker::search_globs();
+        }
+    }
+    if args.location.as_deref() == Some(".") && !config.locations.contains_key(".") {
+        args.location = None;
+        args.cwd = true;
+    }
+    // a folder given by --path is a location named by its path
+    let adhoc_location = match (&args.path, args.cwd || args.stdin) {
+        (Some(path), _) => Some(std::path::absolute(path)?.to_string_lossy().into_owned()),
+        (None, true) => Some(std::env::current_dir()?.to_string_lossy().into_owned()),
+        (None, false) => None,
+    };
+    let mode = if args.stdin { LocationMode::Stdin } else { args.mode };
+    if let Some(path) = adhoc_location.clone().filter(|path| args.stdin || !config.locations.contains_key(path)) {
+        config.locations.insert(path.clone(), Location { path, mode, ..Default::default() });
+    }
+
+    let location_given = args.location.is_some() || adhoc_location.is_some();
     let mut location_name: String = match args.location {
-        None => config.locations.keys().next().unwrap().to_owned(),
+        None => match &adhoc_location {
+            Some(path) => path.clone(),
+            None => config.default_location()?.to_owned(),
+        },
         Some(loc) => {
-            if config.locations.contains_key(&loc) {
-                loc
+            if let Some(name) = config.find_location(&loc) {
+                name.to_owned()
             } else {
-                let mut matches = config.locations.keys()
+                let mut matches = config.enabled_locations().map(|(k, _)| k)
                     .filter(|k| k.to_lowercase().contains(&loc.to_lowercase()));
                 match (matches.next(), matches.next()) {
-                    (Some(_), Some(_)) => fzf_menu(Some(&loc), &config)?,
+                    (Some(_), Some(_)) => choose_location(Some(&loc), &config)?,
                     (Some(name), None) => name.to_owned(),
-                    (None, None) => return Err(anyhow::anyhow!("No location found")),
+                    (None, None) => return Err(BlinkError::LocationNotFound(loc).into()),
                     _ => return Err(anyhow::anyhow!("logic error")),
                 }
             }
         },
     };
 
+    if args.filter.is_some() || !args.ext.is_empty() || !args.glob.is_empty() {
+        let named = match &args.filter {
+            Some(name) => config.filter_patterns(name)?,
+            None => Vec::new(),
+        };
+        let patterns: Vec<String> = named.into_iter().chain(args.ext.iter().cloned()).chain(args.glob.iter().cloned()).collect();
+        let loc = config.locations.get_mut(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        loc.filter = (!patterns.is_empty()).then_some(patterns);
+    }
+    if args.newer.is_some() || args.older.is_some() {
+        let loc = config.locations.get_mut(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        loc.newer = args.newer.clone().or(loc.newer.take());
+        loc.older = args.older.clone().or(loc.older.take());
+    }
+
     if args.create_cache {
         debug!("Creating cache for {}", location_name);
-        let loc = config.locations.get(&location_name).unwrap();
-        io::copy(&mut read_location_with_fd(loc, &config)?, &mut io::stdout())?;
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        let (mut list, watchdog) = read_location_live(loc, &config)?;
+        io::copy(&mut list, &mut io::stdout())?;
+        if watchdog.timed_out() {
+            return Err(anyhow::anyhow!("Scanning {} timed out", loc.path));
+        }
         return Ok(());
     }
 
-    match args.open_path {
-        Some(ref s) => {
-            debug!("execute --open-path={} with location {}", s, location_name);
-            let loc = config.locations.get(&location_name).unwrap();
-            open_folder(&Path::new(&loc.path).join(s).to_string_lossy()).unwrap();
-            return Ok(());
-        },
-        None => (),
+    if args.update_cache {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        let count = cache::update(loc, &config)?;
+        println!("{}: {} entries", location_name, count);
+        return Ok(());
     }
 
-    loop {
-        let loc = config.locations.get(&location_name).unwrap();
-        match fzf_open(&location_name, loc, &config)? {
-            OpenAction::Open(path) => {
-                let s = path.to_string_lossy();
-                debug!("Opening: \"{}\"", s);
-                open_folder(&s).unwrap();
-                return Ok(());
-            }, OpenAction::Menu => {
-                location_name = fzf_menu(None, &config)?;
-                info!("Selected location: {}", location_name);
+    if let Some(ref path) = args.pin {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        pins::pin(&location_name, &pins::entry_for(path, loc))?;
+        return Ok(());
+    }
+
+    if let Some(ref path) = args.unpin {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        pins::unpin(&location_name, &pins::entry_for(path, loc))?;
+        return Ok(());
+    }
+
+    if let Some(ref query) = args.query {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        launcher::print_search(&location_name, loc, &config, query, args.limit, args.format)?;
+        return Ok(());
+    }
+
+    if args.print_entries {
+        let loc = config.locations.get_mut(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        // the toggled mode is kept in a file of the search which started this reload
+        let mode = match args.toggle_mode && picker::in_reload() {
+            true => {
+                let mode = match picker::toggled_mode().unwrap_or(loc.mode) {
+                    LocationMode::Files => LocationMode::Folders,
+                    LocationMode::Folders => LocationMode::Files,
+                    mode => mode,
+                };
+                picker::set_toggled_mode(mode)?;
+                Some(mode)
             },
+            false => picker::toggled_mode(),
+        };
+        if let Some(mode) = mode {
+            *loc = loc.in_mode(mode);
+        }
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        // locations without a cache file are scanned anyway
+        if args.rescan && cache::cache_path(loc).is_some() {
+            match cache::update(loc, &config) {
+                Ok(_) => picker::set_warning(None)?,
+                Err(e) if cache::cache_path(loc).is_some_and(|path| path.exists()) => {
+                    warn!("Rescanning {} failed, showing its cache file: {}", location_name, e);
+                    picker::set_warning(Some(&e.to_string()))?;
+                },
+                Err(e) => return Err(e),
+            }
         }
+        write_results(&location_name, loc, &config, args.matching.as_deref(), picker::in_reload().then(picker::more_file).as_deref(), &mut io::stdout().lock())?;
+        return Ok(());
     }
-}
 
-// Extend Command Builder with with() function
-trait WithFunction {
-    fn with<F>(&mut self, f: F) -> &mut Self
-    where
-        F: FnOnce(&mut Self);
-}
-impl WithFunction for Command {
-    fn with<F>(&mut self, f: F) -> &mut Self
-    where
-        F: FnOnce(&mut Self)
-    {
-        f(self);
-        self
+    if args.revalidate {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        print_revalidated(&location_name, loc, &config)?;
+        return Ok(());
     }
-}
 
-// Extend BufRead with split2() function
-trait Split2Ext: BufRead + Sized {
-    fn split2(self, delim1: u8, delim2: u8) -> Split2<Self>;
-}
-impl<R: BufRead> Split2Ext for R {
-    fn split2(self, delim1: u8, delim2: u8) -> Split2<Self> {
-        Split2 { reader: self, delim: (delim1, delim2) }
+    if args.again {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        again::open_again(&location_name, loc, &config)?;
+        return Ok(());
     }
-}
-struct Split2<R: BufRead> {
-    reader: R,
-    delim: (u8, u8),
-}
-impl<R: BufRead> Iterator for Split2<R> {
-    type Item = Result<Vec<u8>>;
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut buf = Vec::new();
-        loop {
-            let available = match self.reader.fill_buf() {
-                Ok(s) => s,
-                Err(e) => return Some(Err(e.into())),
-            };
-            let (done, used) = match memchr::memchr2(self.delim.0, self.delim.1, available) {
-                Some(i) => {
-                    buf.extend_from_slice(&available[..=i]);
-                    (true, i+1)
+
+    if let Some(ref file) = args.preview_path_file {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        if let Some(entry) = read_selection_file(file)?.first() {
+            preview::print_folder(&entry_path(loc, entry, &config))?;
+        }
+        return Ok(());
+    }
+
+    let selection = match (&args.open_path, &args.open_path_file) {
+        (Some(s), _) => Some(vec![unquote_selection(&paths::to_bytes(s))]),
+        (None, Some(file)) => Some(read_selection_file(file)?),
+        (None, None) => None,
+    };
+    if let Some(selection) = selection {
+        debug!("execute {:?} with location {}", selection, location_name);
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        for entry in selection {
+            let path = entry_path(loc, &entry, &config);
+            match &args.run_binding {
+                Some(key) => {
+                    let action = config.keybindings().remove(key)
+                        .ok_or_else(|| anyhow::anyhow!("No action bound to {}", key))?;
+                    open::run_action(&action, &path, &location_name, loc, &config)?;
                 },
                 None => {
-                    buf.extend_from_slice(available);
-                    (false, available.len())
+                    Opener::new(&config).open_entry(&path, loc)?;
                 },
-            };
-            self.reader.consume(used);
-            if done || used == 0 {
-                break;
             }
         }
-        while buf.last() == Some(&self.delim.0) || buf.last() == Some(&self.delim.1) {
-            buf.pop();
+        return Ok(());
+    }
+
+    let request = match (location_given, args.menu) {
+        (_, true) => ipc::Request::Menu,
+        (true, false) => ipc::Request::Open(location_name.clone()),
+        (false, false) => ipc::Request::Focus,
+    };
+    // the running instance does not know the folder of --path
+    if adhoc_location.is_none() && ipc::forward(&request) {
+        return Ok(());
+    }
+    // without the socket, other invocations start their own search instead of switching this one
+    if let Err(e) = ipc::serve() {
+        warn!("Cannot listen for other invocations: {}", e);
+    }
+
+    if args.menu {
+        location_name = choose_location(None, &config)?;
+    }
+
+    let mut query = None;
+    loop {
+        let loc = config.locations.get(&location_name).ok_or_else(|| BlinkError::LocationNotFound(location_name.clone()))?;
+        if !finder::is_available(loc) {
+            let cached = cache::cache_path(loc).is_some_and(|path| path.exists());
+            if loc.optional {
+                info!("Optional location {} is not available", location_name);
+                location_name = choose_location(None, &config)?;
+                continue;
+            }
+            if !cached {
+                return Err(anyhow::anyhow!("Location {} is not available: {} cannot be reached", location_name, loc.path));
+            }
+            // the entries cannot be opened, but they can still be found
+            warn!("Location {} is not available, showing its cache file", location_name);
+            picker::set_warning(Some(&format!("{} cannot be reached, showing the cache file", loc.path)))?;
+        }
+        config.remember_location(&location_name)?;
+        let action = open_location(&location_name, loc, &config, query.take().as_deref());
+        match ipc::take_request() {
+            Some(ipc::Request::Open(name)) if config.locations.contains_key(&name) => {
+                location_name = name;
+                continue;
+            },
+            Some(_) => {
+                location_name = choose_location(None, &config)?;
+                continue;
+            },
+            None => {},
         }
-        match buf.len() {
-            0 => None,
-            _ => Some(Ok(buf)),
+        match action? {
+            OpenAction::Open(path) => {
+                debug!("Opening: \"{}\"", path.to_string_lossy());
+                match path == Config::path() {
+                    true => open_folder(&path, &config)?,
+                    false if args.print => {
+                        let mut stdout = io::stdout().lock();
+                        stdout.write_all(&paths::to_bytes(&path))?;
+                        stdout.write_all(b"\n")?;
+                    },
+                    false => Opener::new(&config).open_entry(&path, loc)?,
+                }
+                return Ok(());
+            }, OpenAction::Menu(typed) => {
+                location_name = choose_location(None, &config)?;
+                query = typed;
+                info!("Selected location: {}", location_name);
+            }, OpenAction::Actions(path) => {
+                // aborting the action menu returns to the search
+                if let Some(action) = choose_action(&path, &config)? {
+                    open::run_action(&action, &path, &location_name, loc, &config)?;
+                    return Ok(());
+                }
+            },
         }
     }
 }
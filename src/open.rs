@@ -0,0 +1,422 @@
+use std::{env, io::{self, Write}, path::{Path, PathBuf}, process::{Command, Stdio}};
+use anyhow::Result;
+use log::{debug, info};
+use crate::config::{Action, BuiltinAction, Config, Location};
+use crate::error::SpawnTool;
+use crate::filter::Pattern;
+use crate::script::Hooks;
+use crate::{archive, paths, pins, recent, wsl, WithFunction};
+
+/// Opens entries and runs the actions of key bindings, configured by a [`Config`].
+pub struct Opener<'a> {
+    config: &'a Config,
+}
+
+impl<'a> Opener<'a> {
+    pub fn new(config: &'a Config) -> Opener<'a> {
+        Opener { config }
+    }
+
+    /// Opens the path and remembers it in the `recent` location.
+    pub fn open(&self, path: &Path) -> Result<()> {
+        let Some(path) = on_open(path, self.config)? else {
+            return Ok(());
+        };
+        open_folder(&path, self.config)?;
+        recent::record(&path, self.config)
+    }
+
+    /// Opens an entry of the location with its `open_command`, and remembers it in the `recent` location.
+    pub fn open_entry(&self, path: &Path, location: &Location) -> Result<()> {
+        match open_entry(path, location, self.config)? {
+            Some(path) => recent::record(&path, self.config),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs an action like `reveal` or a custom command on an entry of the location.
+    pub fn run_action(&self, action: &Action, path: &Path, location_name: &str, location: &Location) -> Result<()> {
+        run_action(action, path, location_name, location, self.config)
+    }
+}
+
+/// The path to open after the `on_open` hook of the script, or `None` if the script does not want it opened.
+fn on_open(path: &Path, config: &Config) -> Result<Option<PathBuf>> {
+    let path = match Hooks::load(config)? {
+        Some(hooks) => hooks.on_open(path)?,
+        None => Some(path.to_owned()),
+    };
+    if path.is_none() {
+        info!("The script kept the entry from being opened");
+    }
+    Ok(path)
+}
+
+/// Opens an entry of the location with its `open_command`, or else like [`open_folder`].
+/// Returns the opened path, which the `on_open` hook of the script may have changed or vetoed.
+pub fn open_entry(path: &Path, location: &Location, config: &Config) -> Result<Option<PathBuf>> {
+    // members of archives are opened from a temporary copy
+    let extracted = archive::extract_member(path)?;
+    let Some(path) = on_open(extracted.as_deref().unwrap_or(path), config)? else {
+        return Ok(None);
+    };
+    match &location.open_command {
+        Some(command) => open_with(command, &path)?,
+        None => open_folder(&path, config)?,
+    }
+    Ok(Some(path))
+}
+
+/// Opens the path like a double click: with the first matching `open_rules` command,
+/// or else with the default application of the system.
+pub fn open_folder(path: &Path, config: &Config) -> Result<()> {
+    debug!("open_folder({})", path.to_string_lossy());
+
+    if let Some(command) = rule_for(path, config) {
+        return open_with(command, path);
+    }
+
+    let mut cmd = Command::new(system_opener());
+    let native = paths::native_path(path);
+    match wsl::is_wsl() {
+        // the URLs of remote entries are handed to the file manager as they are
+        _ if is_sftp_uri(path) => cmd.arg(path),
+        true => cmd.arg(wsl::to_windows(&native)?),
+        false => cmd.arg(&native),
+    };
+    // stdout may carry JSON answers, e.g. of `--serve-stdio`
+    cmd
+        .stdout(io::stderr())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn_tool()?;
+    Ok(())
+}
+
+/// The program opening paths with their default application.
+pub fn system_opener() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "explorer"
+    } else if wsl::is_wsl() {
+        "explorer.exe"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Finds the first `open_rules` command whose [`Pattern`] matches the path.
+pub fn rule_for<'a>(path: &Path, config: &'a Config) -> Option<&'a str> {
+    let rules = config.open_rules.as_ref()?;
+    rules.iter()
+        .find(|(pattern, _)| match Pattern::new(pattern) {
+            Ok(pattern) => pattern.is_match(path),
+            Err(e) => {
+                debug!("Ignoring invalid open_rules pattern {}: {}", pattern, e);
+                false
+            }
+        })
+        .map(|(_, command)| command.as_str())
+}
+
+/// Runs a command like `okular {path}`. `{path}` is replaced after splitting into arguments,
+/// so paths containing spaces are passed as one argument.
+pub fn open_with(command: &str, path: &Path) -> Result<()> {
+    command_for(command, path)?
+        .stdout(io::stderr())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    Ok(())
+}
+
+fn command_for(command: &str, path: &Path) -> Result<Command> {
+    let mut words = shlex::split(command)
+        .ok_or_else(|| anyhow::anyhow!("Invalid open command: {}", command))?
+        .into_iter();
+    let program = words.next().ok_or_else(|| anyhow::anyhow!("Empty open command"))?;
+    let mut cmd = Command::new(program);
+    for word in words {
+        // a plain `{path}` keeps paths which are not valid UTF-8 intact
+        match word.as_str() {
+            "{path}" => cmd.arg(path),
+            word => cmd.arg(word.replace("{path}", &path.to_string_lossy())),
+        };
+    }
+    if !command.contains("{path}") {
+        cmd.arg(path);
+    }
+    Ok(cmd)
+}
+
+/// Opens the path in `editor`, `$VISUAL` or `$EDITOR`. Terminal editors run in the current terminal
+/// and are waited for, GUI editors are started in the background.
+pub fn open_in_editor(path: &Path, config: &Config) -> Result<()> {
+    let editor = config.editor.clone()
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|e| !e.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("No editor configured. Set $EDITOR or `editor` in the config"))?;
+    let mut cmd = command_for(&editor, path)?;
+    let program = Path::new(cmd.get_program()).file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    let terminal = config.editor_terminal.unwrap_or_else(|| is_terminal_editor(&program));
+    cmd.with(|b| debug!("Executing: {:?}", b));
+    if terminal {
+        cmd.status()?;
+    } else {
+        cmd.spawn()?;
+    }
+    Ok(())
+}
+
+fn is_terminal_editor(program: &str) -> bool {
+    ["vi", "vim", "nvim", "nano", "pico", "micro", "hx", "helix", "kak", "emacs", "ne", "joe", "mg", "ed", "mcedit"]
+        .contains(&program)
+}
+
+/// Runs a bound action on a selected path while the search stays open.
+pub fn run_action(action: &Action, path: &Path, location_name: &str, location: &Location, config: &Config) -> Result<()> {
+    debug!("run_action({:?}, {})", action, path.to_string_lossy());
+    let result = match action {
+        Action::Builtin(BuiltinAction::Open) => open_entry(path, location, config).map(|_| ()),
+        Action::Builtin(BuiltinAction::Reveal) => reveal(path, config),
+        Action::Builtin(BuiltinAction::CopyPath) => copy_to_clipboard(&path.to_string_lossy()),
+        Action::Builtin(BuiltinAction::CopyUri) => copy_to_clipboard(&shareable_uri(path)?),
+        Action::Builtin(BuiltinAction::Terminal) => open_terminal(if path.is_dir() { path } else { path.parent().unwrap_or(path) }),
+        Action::Builtin(BuiltinAction::OpenWith) => open_with_prompt(path),
+        Action::Builtin(BuiltinAction::Editor) => open_in_editor(path, config),
+        Action::Builtin(BuiltinAction::Trash) => move_to_trash(path, location),
+        Action::Builtin(BuiltinAction::Rename) => rename(path, location),
+        Action::Builtin(BuiltinAction::Pin) => pins::toggle(location_name, &pins::entry_for(path, location)),
+        Action::Builtin(_) | Action::Filter { .. } => Err(anyhow::anyhow!("{} can only be used from the search", action)),
+        Action::Command { command } => open_with(command, path),
+    };
+    let opened = matches!(action,
+        Action::Builtin(BuiltinAction::Open | BuiltinAction::Editor | BuiltinAction::OpenWith) | Action::Command { .. });
+    if result.is_ok() && opened {
+        recent::record(path, config)?;
+    }
+    result
+}
+
+/// Opens the folder containing the path in the file manager, with the path selected if supported.
+pub fn reveal(path: &Path, config: &Config) -> Result<()> {
+    debug!("reveal({})", path.to_string_lossy());
+    if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("explorer");
+        // explorer does not understand the quoting of a single "/select,path" argument
+        #[cfg(target_os = "windows")]
+        std::os::windows::process::CommandExt::raw_arg(&mut cmd, format!("/select,\"{}\"", crate::paths::native(&path.to_string_lossy())));
+        cmd
+            .with(|b| debug!("Executing: {:?}", b))
+            .spawn()?;
+        return Ok(());
+    }
+    if wsl::is_wsl() {
+        Command::new("explorer.exe")
+            .arg(format!("/select,{}", wsl::to_windows(path)?))
+            .with(|b| debug!("Executing: {:?}", b))
+            .spawn()?;
+        return Ok(());
+    }
+    if cfg!(target_os = "macos") {
+        Command::new("open")
+            .arg("-R").arg(path)
+            .with(|b| debug!("Executing: {:?}", b))
+            .spawn()?;
+        return Ok(());
+    }
+
+    let shown = match file_uri(path) {
+        Some(uri) => Command::new("dbus-send")
+            .arg("--session")
+            .arg("--dest=org.freedesktop.FileManager1")
+            .arg("--type=method_call")
+            .arg("/org/freedesktop/FileManager1")
+            .arg("org.freedesktop.FileManager1.ShowItems")
+            .arg(format!("array:string:{}", uri))
+            .arg("string:")
+            .with(|b| debug!("Executing: {:?}", b))
+            .status()
+            .is_ok_and(|s| s.success()),
+        None => false,
+    };
+    if !shown {
+        debug!("FileManager1.ShowItems failed, opening the parent folder instead");
+        open_folder(path.parent().unwrap_or(path), config)?;
+    }
+    Ok(())
+}
+
+/// Percent-encoded `file://` URI of an absolute path.
+pub fn file_uri(path: &Path) -> Option<String> {
+    url::Url::from_file_path(path).ok().map(|u| u.to_string())
+}
+
+/// `sftp://` URL of an entry of a remote folder, like `sftp://user@host/srv/data/a%20b.txt`.
+/// Relative folders are in the home folder, like `sftp://host/~/projects/a.txt`.
+pub fn sftp_uri(host: &str, dir: &str, entry: &Path) -> Option<String> {
+    let mut uri = url::Url::parse(&format!("sftp://{}/", host)).ok()?;
+    let home = (!dir.starts_with('/') && !dir.starts_with('~')).then_some("~");
+    uri.path_segments_mut().ok()?
+        .clear()
+        .extend(home)
+        .extend(dir.split('/').filter(|s| !s.is_empty() && *s != "."))
+        .extend(entry.iter().map(|s| s.to_string_lossy()));
+    Some(uri.to_string())
+}
+
+fn is_sftp_uri(path: &Path) -> bool {
+    path.to_str().is_some_and(|p| p.starts_with("sftp://"))
+}
+
+/// `file://` URI of a Windows path like `C:\My Docs` or `\\nas\share`, with the server of UNC paths as host.
+fn windows_file_uri(path: &str) -> Option<String> {
+    let (host, rest) = match path.strip_prefix(r"\\") {
+        Some(unc) => unc.split_once('\\').unwrap_or((unc, "")),
+        None => ("", path),
+    };
+    let mut uri = url::Url::parse("file:///").ok()?;
+    if !host.is_empty() {
+        uri.set_host(Some(host)).ok()?;
+    }
+    uri.path_segments_mut().ok()?.clear().extend(rest.split('\\').filter(|s| !s.is_empty()));
+    Some(uri.to_string())
+}
+
+/// The `file://` URI of the path as other applications see it. On WSL, that is the Windows path.
+fn shareable_uri(path: &Path) -> Result<String> {
+    let uri = match wsl::is_wsl() {
+        true => windows_file_uri(&wsl::to_windows(path)?),
+        false => file_uri(path),
+    };
+    uri.ok_or_else(|| anyhow::anyhow!("{} has no file URI", path.to_string_lossy()))
+}
+
+fn open_terminal(dir: &Path) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg("start").arg("cmd");
+        cmd
+    } else if cfg!(target_os = "macos") {
+        let mut cmd = Command::new("open");
+        cmd.arg("-a").arg("Terminal").arg(dir);
+        cmd
+    } else {
+        Command::new(env::var("TERMINAL").unwrap_or_else(|_| "x-terminal-emulator".to_owned()))
+    };
+    cmd
+        .current_dir(dir)
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    Ok(())
+}
+
+/// Moves the path to the trash after asking for confirmation. Requires `allow_delete` on the location.
+fn move_to_trash(path: &Path, location: &Location) -> Result<()> {
+    if !location.allow_delete {
+        return Err(anyhow::anyhow!("Deleting is not allowed in {}. Set allow_delete: true to enable it", location.path));
+    }
+    if !confirm(&format!("Move {} to the trash?", path.to_string_lossy()))? {
+        return Ok(());
+    }
+    debug!("Moving to trash: {}", path.to_string_lossy());
+    trash::delete(path)?;
+    Ok(())
+}
+
+/// Asks for a new name and renames the path. Names containing slashes move it relative to its folder.
+fn rename(path: &Path, location: &Location) -> Result<()> {
+    if !location.allow_delete {
+        return Err(anyhow::anyhow!("Renaming is not allowed in {}. Set allow_delete: true to enable it", location.path));
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    eprint!("Rename {} to (empty to cancel): ", name);
+    let mut new_name = String::new();
+    std::io::stdin().read_line(&mut new_name)?;
+    let new_name = new_name.trim();
+    if new_name.is_empty() || new_name == name {
+        return Ok(());
+    }
+    let target = path.parent().unwrap_or(Path::new("")).join(new_name);
+    if target.exists() {
+        return Err(anyhow::anyhow!("{} already exists", target.to_string_lossy()));
+    }
+    debug!("Renaming {} to {}", path.to_string_lossy(), target.to_string_lossy());
+    std::fs::rename(path, &target)?;
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Shows the "Open with" dialog on Windows. Elsewhere, asks for a command on the terminal.
+fn open_with_prompt(path: &Path) -> Result<()> {
+    if cfg!(target_os = "windows") {
+        Command::new("rundll32")
+            .arg(format!("shell32.dll,OpenAs_RunDLL {}", path.to_string_lossy()))
+            .with(|b| debug!("Executing: {:?}", b))
+            .spawn()?;
+        return Ok(());
+    }
+    eprint!("Open {} with: ", path.to_string_lossy());
+    let mut command = String::new();
+    std::io::stdin().read_line(&mut command)?;
+    match command.trim() {
+        "" => Ok(()),
+        command => open_with(command, path),
+    }
+}
+
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        Command::new("clip")
+    } else if cfg!(target_os = "macos") {
+        Command::new("pbcopy")
+    } else if env::var_os("WAYLAND_DISPLAY").is_some() {
+        Command::new("wl-copy")
+    } else {
+        let mut cmd = Command::new("xclip");
+        cmd.arg("-selection").arg("clipboard");
+        cmd
+    };
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    child.wait()?;
+    Ok(())
+}
+
+#[test]
+fn open_rule_matching() {
+    let config = Config {
+        open_rules: Some([
+            ("*.log", "code {path}"),
+            ("pdf", "okular"),
+            ("**/notes/*.md", "typora"),
+        ].into_iter().map(|(p, c)| (p.to_owned(), c.to_owned())).collect()),
+        ..Default::default()
+    };
+
+    assert_eq!(rule_for(Path::new("/var/app/Server.LOG"), &config), Some("code {path}"));
+    assert_eq!(rule_for(Path::new("docs/paper.pdf"), &config), Some("okular"));
+    assert_eq!(rule_for(Path::new("/home/me/notes/todo.md"), &config), Some("typora"));
+    assert_eq!(rule_for(Path::new("/home/me/readme.md"), &config), None);
+}
+
+#[test]
+fn windows_file_uris() {
+    assert_eq!(windows_file_uri(r"C:\My Docs\a#1.txt").as_deref(), Some("file:///C:/My%20Docs/a%231.txt"));
+    assert_eq!(windows_file_uri(r"\\nas\share\Café.pdf").as_deref(), Some("file://nas/share/Caf%C3%A9.pdf"));
+}
+
+#[test]
+fn sftp_uris() {
+    assert_eq!(sftp_uri("me@nas", "/srv/data/", Path::new("My Docs/a.txt")).as_deref(), Some("sftp://me@nas/srv/data/My%20Docs/a.txt"));
+    assert_eq!(sftp_uri("nas", ".", Path::new("a.txt")).as_deref(), Some("sftp://nas/~/a.txt"));
+    assert_eq!(sftp_uri("nas", "projects", Path::new("a.txt")).as_deref(), Some("sftp://nas/~/projects/a.txt"));
+    assert_eq!(sftp_uri("nas", "~/projects", Path::new("a.txt")).as_deref(), Some("sftp://nas/~/projects/a.txt"));
+}
@@ -0,0 +1,136 @@
+use std::{borrow::Cow, path::{Path, PathBuf}};
+
+/// Converts separators to the native ones and removes duplicate and trailing separators.
+/// Prefixes like `\\server\share`, `\\?\` and `C:` are kept intact.
+pub fn native(path: &str) -> String {
+    if cfg!(target_os = "windows") { to_windows(path) } else { to_unix(path) }
+}
+
+/// Like [`native`], but keeps paths which are not valid Unicode unchanged.
+pub fn native_path(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(s) => PathBuf::from(native(s)),
+        None => path.to_owned(),
+    }
+}
+
+/// A path read from a list. On Unix, paths are bytes and do not have to be valid UTF-8.
+#[cfg(unix)]
+pub fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+pub fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// The path as written to a list, see [`from_bytes`].
+#[cfg(unix)]
+pub fn to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+pub fn to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+/// Cleans up an entry printed by a finder: trims whitespace and leading `./`, and replaces control characters,
+/// which would break the list into several entries. Returns `None` for entries which are not a path.
+pub fn clean_entry(entry: &[u8]) -> Option<PathBuf> {
+    let mut entry = entry.trim_ascii();
+    while let Some(rest) = entry.strip_prefix(b"./").or_else(|| entry.strip_prefix(b".\\")) {
+        entry = rest;
+    }
+    if entry.is_empty() || entry == b"." {
+        return None;
+    }
+    let mut cleaned = Vec::with_capacity(entry.len());
+    for &b in entry {
+        match b.is_ascii_control() {
+            true => cleaned.extend_from_slice("\u{FFFD}".as_bytes()),
+            false => cleaned.push(b),
+        }
+    }
+    Some(from_bytes(cleaned))
+}
+
+/// Whether [`clean_entry`] would leave the entry as it is.
+pub fn is_clean_entry(entry: &[u8]) -> bool {
+    match (entry.first(), entry.last()) {
+        (Some(first), Some(last)) => !first.is_ascii_whitespace() && !last.is_ascii_whitespace()
+            && !entry.starts_with(b"./") && !entry.starts_with(b".\\") && entry != b"."
+            && !entry.iter().any(|b| b.is_ascii_control()),
+        _ => false,
+    }
+}
+
+fn is_sep(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// Splits off the start of a Windows path which is not a plain list of folder names:
+/// `\\?\` long path and `\\.\` device prefixes, the `\\` of UNC paths, and drives like `C:\` or
+/// the drive-relative `C:`.
+fn split_windows_prefix(path: &str) -> (String, &str) {
+    let mut chars = path.chars();
+    match (chars.next(), chars.next(), chars.next(), chars.next()) {
+        (Some(a), Some(b), Some(kind @ ('?' | '.')), Some(d)) if is_sep(a) && is_sep(b) && is_sep(d) =>
+            (format!("\\\\{}\\", kind), &path[4..]),
+        (Some(a), Some(b), _, _) if is_sep(a) && is_sep(b) => ("\\\\".to_owned(), &path[2..]),
+        (Some(a), _, _, _) if is_sep(a) => ("\\".to_owned(), &path[1..]),
+        (Some(drive), Some(':'), Some(c), _) if drive.is_ascii_alphabetic() && is_sep(c) =>
+            (format!("{}:\\", drive), &path[3..]),
+        (Some(drive), Some(':'), _, _) if drive.is_ascii_alphabetic() => (format!("{}:", drive), &path[2..]),
+        _ => (String::new(), path),
+    }
+}
+
+pub fn to_windows(path: &str) -> String {
+    let (prefix, rest) = split_windows_prefix(path);
+    let parts: Vec<&str> = rest.split(is_sep).filter(|p| !p.is_empty()).collect();
+    prefix + parts.join("\\").as_str()
+}
+
+pub fn to_unix(path: &str) -> String {
+    let prefix = if path.starts_with(is_sep) { "/" } else { "" };
+    let parts: Vec<&str> = path.split(is_sep).filter(|p| !p.is_empty()).collect();
+    prefix.to_owned() + parts.join("/").as_str()
+}
+
+#[test]
+fn windows_paths() {
+    assert_eq!(to_windows("C:/Users//me/"), "C:\\Users\\me");
+    assert_eq!(to_windows("C:\\"), "C:\\");
+    assert_eq!(to_windows("C:docs\\a.txt"), "C:docs\\a.txt");
+    assert_eq!(to_windows("\\\\nas.local\\share\\\\sub/dir\\"), "\\\\nas.local\\share\\sub\\dir");
+    assert_eq!(to_windows("//nas.local/share/sub"), "\\\\nas.local\\share\\sub");
+    assert_eq!(to_windows("\\\\?\\C:\\very\\\\long/path"), "\\\\?\\C:\\very\\long\\path");
+    assert_eq!(to_windows("\\\\?\\UNC\\nas\\share\\x"), "\\\\?\\UNC\\nas\\share\\x");
+    assert_eq!(to_windows("\\\\.\\pipe\\blink"), "\\\\.\\pipe\\blink");
+    assert_eq!(to_windows("/Users/me"), "\\Users\\me");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_entries() {
+    let entry = clean_entry(b"./caf\xe9/menu.txt\r").unwrap();
+    assert_eq!(to_bytes(&entry), &b"caf\xe9/menu.txt"[..]);
+    assert_eq!(to_bytes(&clean_entry(b"a\tb").unwrap()), "a\u{FFFD}b".as_bytes());
+    assert_eq!(clean_entry(b" . "), None);
+    assert!(is_clean_entry(b"caf\xe9/menu.txt"));
+    assert!(!is_clean_entry(b"./caf\xe9/menu.txt\r") && !is_clean_entry(b"a\tb") && !is_clean_entry(b""));
+}
+
+#[test]
+fn unix_paths() {
+    assert_eq!(to_unix("/home//me/docs/"), "/home/me/docs");
+    assert_eq!(to_unix("/"), "/");
+    assert_eq!(to_unix("sub\\dir/a.txt"), "sub/dir/a.txt");
+}
@@ -0,0 +1,438 @@
+use std::{env, io::{self, Read, Write}, path::{Path, PathBuf}, process::{Command, Stdio}, sync::mpsc, thread, time::Duration};
+use anyhow::Result;
+use log::debug;
+use blink_search::config::{Action, BuiltinAction, Config, HistorySettings, Location, LocationMode, PickerCommand, PickerKind, Source};
+use clap::ValueEnum;
+use blink_search::error::{BlinkError, SpawnTool};
+use blink_search::{cache, history, tool, WithFunction};
+use crate::{dmenu, ipc};
+
+/// Writes the entries into the picker. Runs in its own thread, so the picker is shown right away.
+pub type Entries = Box<dyn FnOnce(&mut dyn Write) -> Result<()> + Send>;
+
+/// The lists shown by blink.
+pub enum Pick<'a> {
+    /// The entries of a location, with its key bindings, starting with the query if given.
+    Search { name: &'a str, location: &'a Location, query: Option<&'a str> },
+    /// The location menu, starting with the query if the picker supports it.
+    Locations { query: Option<&'a str> },
+    /// The `action_menu` for an entry.
+    Actions { path: &'a Path },
+    /// The folders offered as first location by the setup.
+    Folder,
+    /// Entries of the location opened before, several of which can be chosen.
+    Again { name: &'a str },
+}
+
+/// What was chosen in a picker.
+pub enum Choice {
+    /// A line of the entries, as printed by the picker.
+    Entry(Vec<u8>),
+    /// The `menu` key binding, with the query typed so far.
+    Menu(Option<String>),
+    /// The `edit-config` key binding.
+    EditConfig,
+    /// The `actions` key binding on an entry.
+    Actions(Vec<u8>),
+    /// All lines chosen in a [`Pick::Again`].
+    Entries(Vec<Vec<u8>>),
+}
+
+/// A front-end showing a list, like fzf or rofi.
+pub trait Picker {
+    /// Shows the entries and waits for a choice. Closing the picker is [`BlinkError::Aborted`].
+    fn pick(&self, pick: Pick, entries: Entries) -> Result<Choice>;
+}
+
+/// The `picker` of the config.
+pub fn for_config(config: &Config) -> Box<dyn Picker + '_> {
+    match config.picker.kind() {
+        PickerKind::Fzf => Box::new(Fzf { config }),
+        kind => Box::new(dmenu::Dmenu(kind)),
+    }
+}
+
+/// Entries which are known up front, like the labels of a menu.
+pub fn lines(lines: Vec<String>) -> Entries {
+    Box::new(move |out| {
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        Ok(())
+    })
+}
+
+/// Passes the id of the search to the `--print-entries` reloads of fzf, which share its files.
+const SEARCH_ID_VAR: &str = "BLINK_SEARCH_ID";
+
+/// The location of the search for the commands of its key bindings, which read it with `--search-env`,
+/// so no location name or path is parsed by the shell.
+const LOCATION_VAR: &str = "BLINK_LOCATION";
+/// The folder of a search started with `--path`, instead of [`LOCATION_VAR`].
+const PATH_VAR: &str = "BLINK_PATH";
+
+/// The `--ext` and `--glob` patterns of the search, one per line.
+const GLOB_VAR: &str = "BLINK_GLOB";
+
+/// The `--newer` and `--older` limits of the search.
+const NEWER_VAR: &str = "BLINK_NEWER";
+const OLDER_VAR: &str = "BLINK_OLDER";
+
+/// The location name, or the folder of `--path`, of the search which started this command.
+pub fn search_location() -> (Option<String>, Option<PathBuf>) {
+    (env::var(LOCATION_VAR).ok(), env::var_os(PATH_VAR).map(PathBuf::from))
+}
+
+/// The `--newer` and `--older` limits of the search which started this command.
+pub fn search_limits() -> (Option<String>, Option<String>) {
+    (env::var(NEWER_VAR).ok(), env::var(OLDER_VAR).ok())
+}
+
+/// The patterns the list of the search which started this command is filtered with.
+pub fn search_globs() -> Vec<String> {
+    env::var(GLOB_VAR).unwrap_or_default().lines().map(str::to_owned).collect()
+}
+
+/// A temporary file of the running search, like `blink-more-<id>.txt`. Reloads started by fzf get the same file.
+fn search_file(kind: &str) -> PathBuf {
+    let id = env::var(SEARCH_ID_VAR).unwrap_or_else(|_| std::process::id().to_string());
+    env::temp_dir().join(format!("blink-{}-{}.txt", kind, id))
+}
+
+/// Whether blink was started by a reload of the search.
+pub fn in_reload() -> bool {
+    env::var_os(SEARCH_ID_VAR).is_some()
+}
+
+/// Removes the temporary files once the search is closed.
+pub fn remove_search_files() {
+    for kind in ["more", "mode", "warning"] {
+        let _ = std::fs::remove_file(search_file(kind));
+    }
+}
+
+/// The file the fzf header is read from, see [`write_header`].
+pub fn more_file() -> PathBuf {
+    search_file("more")
+}
+
+/// How long the reload listing the matches of a query waits for typing to pause, fzf stops it when the query changes.
+pub const MATCHES_DELAY: Duration = Duration::from_millis(200);
+
+/// The entries of `list` matching the query like in the search, best matches first, as listed by `fzf --filter`.
+pub fn filter_matches(query: &str, mut list: impl Read + Send + 'static, config: &Config) -> Result<impl Read> {
+    let picker = config.picker.command();
+    let mut fzf = tool(&picker.binary, config)
+        .args(&picker.args)
+        .args(config.fzf_flags.as_deref().unwrap_or_default())
+        .arg("--scheme=path")
+        .arg(format!("--filter={}", query))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn_tool()?;
+    let mut stdin = fzf.stdin.take().unwrap();
+    thread::spawn(move || io::copy(&mut list, &mut stdin));
+    Ok(fzf.stdout.take().unwrap())
+}
+
+/// The mode the location is shown in after the `toggle-mode` key binding.
+pub fn toggled_mode() -> Option<LocationMode> {
+    let mode = std::fs::read_to_string(search_file("mode")).ok()?;
+    LocationMode::from_str(mode.trim(), true).ok()
+}
+
+pub fn set_toggled_mode(mode: LocationMode) -> Result<()> {
+    std::fs::write(search_file("mode"), mode.to_string())?;
+    Ok(())
+}
+
+/// Shows the warning above the key hints until the search is closed, or removes it, e.g. that the cache file
+/// is shown as the location cannot be scanned.
+pub fn set_warning(warning: Option<&str>) -> Result<()> {
+    match warning {
+        Some(warning) => std::fs::write(search_file("warning"), warning)?,
+        None => {
+            let _ = std::fs::remove_file(search_file("warning"));
+        },
+    }
+    Ok(())
+}
+
+fn warning() -> Option<String> {
+    std::fs::read_to_string(search_file("warning")).ok()
+}
+
+/// The fzf header: a warning, the key hints, the age of the cache file and the number of entries left out of the search.
+pub fn header(location: &Location, config: &Config, more: usize) -> String {
+    let mut lines: Vec<String> = warning().into_iter().collect();
+    lines.push(key_hints(location, config));
+    lines.extend(cache_hint(location, config));
+    if more > 0 {
+        lines.push(format!("+{} more, refine your query", more));
+    }
+    lines.retain(|line| !line.is_empty());
+    lines.join("\n")
+}
+
+/// Writes the [`header`] for fzf to read once the list is loaded.
+pub fn write_header(path: &Path, location: &Location, config: &Config, more: usize) -> Result<()> {
+    std::fs::write(path, header(location, config, more))?;
+    Ok(())
+}
+
+/// Whether the header changes with the list, so fzf reads it from the [`more_file`] once the list is loaded.
+pub fn has_dynamic_header(location: &Location, config: &Config) -> bool {
+    config.max_results(location).is_some() || cache::cache_path(location).is_some() || scan_hint(location).is_some()
+}
+
+/// Tells that the location is being scanned, like `scanning /mnt/share...`, as the list may take long to fill
+/// without a cache file. Replaced by the [`header`] once the list is loaded.
+fn scan_hint(location: &Location) -> Option<String> {
+    let cached = cache::cache_path(location).is_some_and(|path| path.exists()) && location.source != Source::Merged;
+    if cached || matches!(location.mode, LocationMode::Stdin | LocationMode::Group) {
+        return None;
+    }
+    Some(match location.remote.as_ref().unwrap_or(&location.path) {
+        path if path.is_empty() => "scanning...".to_owned(),
+        path => format!("scanning {}...", path),
+    })
+}
+
+/// How old the cache file is, like `cache: 3 days old, ctrl-r to refresh`.
+fn cache_hint(location: &Location, config: &Config) -> Option<String> {
+    let age = cache::cache_path(location).and_then(|path| cache::cache_age(&path))?;
+    let reload = config.keybindings().into_iter().find(|(_, action)| *action == Action::Builtin(BuiltinAction::Reload));
+    Some(match reload {
+        Some((key, _)) => format!("cache: {} old, {} to refresh", cache::format_age(age), key),
+        None => format!("cache: {} old", cache::format_age(age)),
+    })
+}
+
+/// The key bindings shown above the search, like `tab: switch location, ctrl-x: reveal`.
+pub fn key_hints(location: &Location, config: &Config) -> String {
+    if config.ui.as_ref().and_then(|ui| ui.key_hints) == Some(false) {
+        return String::new();
+    }
+    keybindings(location, config).iter()
+        .map(|(key, action)| format!("{}: {}", key, action.hint()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The key bindings of the search of the location. A piped list cannot be listed again, so it has none which
+/// reload the list, as they would scan the current folder instead.
+fn keybindings(location: &Location, config: &Config) -> Vec<(String, Action)> {
+    config.keybindings().into_iter()
+        .filter(|(_, action)| location.mode != LocationMode::Stdin
+            || !matches!(action, Action::Builtin(BuiltinAction::Reload | BuiltinAction::ToggleMode) | Action::Filter { .. }))
+        .collect()
+}
+
+/// Runs the picker with the entries streamed to its stdin, and returns its exit code and output lines.
+/// The search of a location can be closed by another invocation requesting a different one.
+pub fn run_picker(cmd: &mut Command, entries: Entries, search: bool) -> Result<(Option<i32>, Vec<Vec<u8>>)> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn_tool()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let child = match search {
+        true => {
+            ipc::track_picker(child);
+            None
+        },
+        false => Some(child),
+    };
+
+    let (error_sender, errors) = mpsc::channel();
+    thread::spawn(move || match entries(&mut stdin) {
+        // the picker closing the pipe early is expected
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+            debug!("Stopped writing entries: {}", e),
+        Err(e) => {
+            let _ = error_sender.send(e);
+            if search {
+                ipc::close_picker();
+            }
+        },
+        Ok(()) => (),
+    });
+
+    let mut output = Vec::new();
+    stdout.read_to_end(&mut output)?;
+    let status = match child {
+        Some(mut child) => child.wait()?,
+        None => ipc::wait_picker()?,
+    };
+    if let Ok(e) = errors.try_recv() {
+        return Err(e);
+    }
+    debug!("Picker output: {:?}", String::from_utf8_lossy(&output));
+    let lines = output.split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_vec())
+        .collect();
+    Ok((status.code(), lines))
+}
+
+/// fzf, or a custom fzf-like picker.
+pub struct Fzf<'a> {
+    config: &'a Config,
+}
+
+impl Picker for Fzf<'_> {
+    fn pick(&self, pick: Pick, entries: Entries) -> Result<Choice> {
+        let this_exe = env::current_exe()?;
+        let config = self.config;
+        let picker = config.picker.command();
+        let mut cmd = tool(&picker.binary, config);
+        cmd.args(&picker.args);
+        match &pick {
+            Pick::Search { name, location, query } => {
+                let history = history::path(name)?;
+                // folders opened with --path are no locations of the config
+                match location.path == *name {
+                    true => cmd.env(PATH_VAR, name).env_remove(LOCATION_VAR),
+                    false => cmd.env(LOCATION_VAR, name).env_remove(PATH_VAR),
+                };
+                // reloaded lists keep the --ext and --glob filter
+                match &location.filter {
+                    Some(patterns) => cmd.env(GLOB_VAR, patterns.join("\n")),
+                    None => cmd.env_remove(GLOB_VAR),
+                };
+                // reloaded lists keep the --newer and --older limits
+                for (var, limit) in [(NEWER_VAR, &location.newer), (OLDER_VAR, &location.older)] {
+                    match limit {
+                        Some(limit) => cmd.env(var, limit),
+                        None => cmd.env_remove(var),
+                    };
+                }
+                let location_arg = "--search-env";
+                cmd
+                    .with(|b| if config.picker.is_fzf() {
+                        b.arg("--scheme=path").arg(format!("--prompt={}> ", name));
+                        let header = [header(location, config, 0)].into_iter()
+                            .chain(scan_hint(location))
+                            .filter(|line| !line.is_empty())
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                        if !header.is_empty() {
+                            b.arg(format!("--header={}", header));
+                        }
+                        b.env(SEARCH_ID_VAR, std::process::id().to_string());
+                        if has_dynamic_header(location, config) {
+                            let show = if cfg!(windows) { "type" } else { "cat" };
+                            b.arg(format!("--bind=load:transform-header({} \"{}\")", show, more_file().display()));
+                        }
+                        if location.mode != LocationMode::Stdin && config.max_results(location).is_some() {
+                            // entries left out are searched by listing the best matches again once typing pauses
+                            b.arg(format!("--bind=change:reload(\"{}\" --print-entries --matching={{q}} {})", this_exe.display(), location_arg));
+                        }
+                        if location.mode == LocationMode::Folders {
+                            b.arg(format!("--preview=\"{}\" --preview-path-file={{f}} {}", this_exe.display(), location_arg));
+                        }
+                        if location.revalidate && location.cache_file.is_some() {
+                            // keep showing the cached list until the merged one is complete
+                            b.arg(format!("--bind=start:reload-sync(\"{}\" --revalidate {})", this_exe.display(), location_arg));
+                        }
+                    })
+                    .args(history_args(&picker, &history, config.history_settings(Some(location)), config.picker.is_fzf())?)
+                    .args(query.and_then(|q| picker.query_arg(q)))
+                    .with(|b| for (key, action) in keybindings(location, config) {
+                        b.args(picker.bind_arg(&key, &fzf_action(&key, &action, &this_exe, location_arg)));
+                    });
+            },
+            Pick::Locations { query } => {
+                cmd
+                    .args(history_args(&picker, &history::menu_path(), config.history_settings(None), config.picker.is_fzf())?)
+                    .args(picker.bind_arg("tab", "accept"))
+                    .args(query.and_then(|q| picker.query_arg(q)));
+            },
+            Pick::Again { name } => {
+                cmd.with(|b| if config.picker.is_fzf() {
+                    b.arg("--multi").arg("--scheme=path").arg(format!("--header=Open again from {}, tab selects several", name));
+                });
+            },
+            Pick::Folder => {
+                cmd.with(|b| if config.picker.is_fzf() {
+                    b.arg("--header=Folder of the new location").arg("--scheme=path");
+                });
+            },
+            Pick::Actions { path } => {
+                cmd.with(|b| if config.picker.is_fzf() {
+                    b.arg(format!("--header={}", path.to_string_lossy())).arg("--no-sort");
+                });
+            },
+        }
+        if let Some(ui) = config.ui.as_ref().filter(|_| config.picker.is_fzf()) {
+            let location = match &pick {
+                Pick::Search { name, .. } | Pick::Again { name } => Some(*name),
+                _ => None,
+            };
+            cmd.args(ui.fzf_args(location));
+        }
+        cmd.args(config.fzf_flags.as_ref().unwrap_or(&Vec::new()));
+
+        let search = matches!(pick, Pick::Search { .. });
+        let (code, lines) = run_picker(&mut cmd, entries, search)?;
+        if matches!(pick, Pick::Again { .. }) {
+            return match (code, lines) {
+                (Some(0), lines) if !lines.is_empty() => Ok(Choice::Entries(lines)),
+                (Some(0 | 1 | 130) | None, _) => Err(BlinkError::Aborted.into()),
+                (code, _) => Err(anyhow::anyhow!("{} exited with code {:?}", picker.binary, code)),
+            };
+        }
+        let choice = lines.into_iter().next().map(|line| match line.as_slice() {
+            // printed by --print-marker, with the query as typed
+            s if search && (s == b"MENU" || s.starts_with(b"MENU ")) => {
+                let query = String::from_utf8_lossy(s.strip_prefix(b"MENU ").unwrap_or_default()).into_owned();
+                Choice::Menu((!query.is_empty()).then_some(query))
+            },
+            b"EDIT_CONFIG" if search => Choice::EditConfig,
+            s if search && s.starts_with(b"ACTIONS ") => Choice::Actions(s[b"ACTIONS ".len()..].to_vec()),
+            _ => Choice::Entry(line),
+        });
+        // the markers of key bindings are printed before fzf aborts
+        match (code, choice) {
+            (_, Some(choice)) => Ok(choice),
+            // no match, closed with esc, or killed by another invocation
+            (Some(1 | 130) | None, None) => Err(BlinkError::Aborted.into()),
+            (code, None) => Err(anyhow::anyhow!("{} exited with code {:?}", picker.binary, code)),
+        }
+    }
+}
+
+/// The history arguments of the picker, after trimming the history file to `max_entries`.
+fn history_args(picker: &PickerCommand, path: &Path, settings: HistorySettings, fzf: bool) -> Result<Vec<String>> {
+    if settings.enabled == Some(false) {
+        return Ok(Vec::new());
+    }
+    let mut args: Vec<String> = picker.history_arg(path).into_iter().collect();
+    if let Some(max_entries) = settings.max_entries {
+        history::trim(path, max_entries)?;
+        if fzf && !args.is_empty() {
+            args.push(format!("--history-size={}", max_entries));
+        }
+    }
+    Ok(args)
+}
+
+/// Translates a key binding into an fzf action. Actions which leave the search print a marker with `--print-marker`,
+/// which is read by [`Fzf::pick`], `filter`, `reload` and `toggle-mode` reload the entries, and all others run `--open-path-file` on the selection. fzf writes the selection
+/// to the temporary file of `{+f}`, so no path is ever parsed by the shell. `location_arg` selects the location of the search
+/// with `--search-env`.
+fn fzf_action(key: &str, action: &Action, this_exe: &Path, location_arg: &str) -> String {
+    match action {
+        Action::Builtin(BuiltinAction::Menu) => format!("execute(\"{}\" --print-marker=MENU --marker-text={{q}})+abort", this_exe.display()),
+        Action::Builtin(BuiltinAction::EditConfig) => "execute(echo EDIT_CONFIG)+abort".to_owned(),
+        Action::Builtin(BuiltinAction::Actions) => format!("execute(\"{}\" --print-marker=ACTIONS --marker-file={{f}})+abort", this_exe.display()),
+        Action::Builtin(BuiltinAction::ToggleMode) => format!("reload(\"{}\" --print-entries --toggle-mode {})", this_exe.display(), location_arg),
+        Action::Builtin(BuiltinAction::Reload) => format!("reload(\"{}\" --print-entries --rescan {})", this_exe.display(), location_arg),
+        Action::Filter { .. } => format!("reload(\"{}\" --print-entries --run-binding={} {})", this_exe.display(), key, location_arg),
+        _ => format!("execute(\"{}\" --run-binding={} --open-path-file={{+f}} {})", this_exe.display(), key, location_arg),
+    }
+}
@@ -0,0 +1,70 @@
+use std::{io::Write, path::{Path, PathBuf}};
+use anyhow::Result;
+use log::debug;
+use crate::config::{Config, Location};
+use crate::{cache, location_to_id};
+
+/// Prefix of pinned entries in the fzf list.
+pub const MARKER: &str = "★ ";
+
+pub fn path(location_name: &str) -> Result<PathBuf> {
+    Ok(Config::base_dir().join(format!("pins-{}.txt", location_to_id(location_name)?)))
+}
+
+pub fn load(location_name: &str) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path(location_name)?).unwrap_or_default();
+    Ok(content.lines().filter(|l| !l.is_empty()).map(|l| l.to_owned()).collect())
+}
+
+fn save(location_name: &str, pins: &[String]) -> Result<()> {
+    let mut content = pins.join("\n");
+    content.push('\n');
+    std::fs::write(path(location_name)?, content)?;
+    Ok(())
+}
+
+/// The entry of a path as shown in the list, i.e. relative to the location.
+pub fn entry_for(path: &Path, location: &Location) -> String {
+    path.strip_prefix(&location.path).unwrap_or(path).to_string_lossy().into_owned()
+}
+
+/// The entry without the marker of pins or of entries only found in the cache.
+pub fn strip_marker(s: &[u8]) -> &[u8] {
+    s.strip_prefix(MARKER.as_bytes())
+        .or_else(|| s.strip_prefix(cache::CACHE_ONLY_MARKER.as_bytes()))
+        .unwrap_or(s)
+}
+
+pub fn pin(location_name: &str, entry: &str) -> Result<()> {
+    let mut pins = load(location_name)?;
+    if !pins.iter().any(|p| p == entry) {
+        debug!("Pinning {} in {}", entry, location_name);
+        pins.push(entry.to_owned());
+        save(location_name, &pins)?;
+    }
+    Ok(())
+}
+
+pub fn unpin(location_name: &str, entry: &str) -> Result<()> {
+    let mut pins = load(location_name)?;
+    debug!("Unpinning {} in {}", entry, location_name);
+    pins.retain(|p| p != entry);
+    save(location_name, &pins)
+}
+
+/// Pins the entry, or unpins it if it is already pinned.
+pub fn toggle(location_name: &str, entry: &str) -> Result<()> {
+    match load(location_name)?.iter().any(|p| p == entry) {
+        true => unpin(location_name, entry),
+        false => pin(location_name, entry),
+    }
+}
+
+/// Writes the marked pins of the location, which come before its entries.
+pub fn write_pins(location_name: &str, out: &mut impl Write) -> Result<()> {
+    for pin in load(location_name)? {
+        writeln!(out, "{}{}", MARKER, pin)?;
+    }
+    Ok(())
+}
+
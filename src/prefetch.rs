@@ -0,0 +1,114 @@
+use std::{collections::HashMap, io::{self, Read}, sync::{mpsc::{self, Receiver}, Mutex}, thread};
+use log::debug;
+use blink_search::config::{Config, LocationMode};
+use blink_search::history;
+use blink_search::index::read_location_marked;
+
+/// How many of the most searched other locations are prefetched, unless `prefetch` is set.
+const DEFAULT_COUNT: usize = 2;
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks read ahead per location, 64 MB, after which the scan waits until the location is shown.
+const BUFFERED_CHUNKS: usize = 1024;
+
+/// The chunks of entries of a location, as they are read.
+type Chunks = Receiver<io::Result<Vec<u8>>>;
+
+/// The entries of the prefetched locations, by location name.
+static PREFETCHED: Mutex<Option<HashMap<String, Chunks>>> = Mutex::new(None);
+
+/// The other locations searched most often, going by the number of queries in their history.
+fn predict(current: Option<&str>, config: &Config) -> Vec<String> {
+    let mut locations: Vec<(usize, &String)> = config.enabled_locations()
+        .filter(|(name, loc)| Some(name.as_str()) != current && loc.mode != LocationMode::Command)
+        .filter_map(|(name, _)| {
+            let queries = std::fs::read_to_string(history::path(name).ok()?).unwrap_or_default()
+                .lines().filter(|l| !l.is_empty()).count();
+            (queries > 0).then_some((queries, name))
+        })
+        .collect();
+    // most queries first, then by name
+    locations.sort_by(|(a, a_name), (b, b_name)| b.cmp(a).then(a_name.cmp(b_name)));
+    locations.into_iter().take(config.prefetch.unwrap_or(DEFAULT_COUNT)).map(|(_, name)| name.clone()).collect()
+}
+
+/// Starts reading the entries of the locations likely shown next, each in a background thread, so switching
+/// to them with tab shows them at once. Locations which are already being read are skipped.
+pub fn start(current: Option<&str>, config: &Config) {
+    let mut prefetched = PREFETCHED.lock().unwrap();
+    let prefetched = prefetched.get_or_insert_default();
+    for name in predict(current, config) {
+        if prefetched.contains_key(&name) {
+            continue;
+        }
+        debug!("Prefetching {}", name);
+        let (sender, receiver) = mpsc::sync_channel(BUFFERED_CHUNKS);
+        let (thread_name, location, config) = (name.clone(), config.locations[&name].clone(), config.clone());
+        thread::spawn(move || {
+            let mut list = match read_location_marked(&thread_name, &location, &config) {
+                Ok(list) => list,
+                Err(e) => {
+                    let _ = sender.send(Err(io::Error::other(e)));
+                    return;
+                },
+            };
+            loop {
+                let mut chunk = vec![0; CHUNK_SIZE];
+                let chunk = match list.read(&mut chunk) {
+                    Ok(0) => return,
+                    Ok(len) => {
+                        chunk.truncate(len);
+                        Ok(chunk)
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                // stops once the prefetched entries are dropped, closing the list like an aborted search
+                if sender.send(chunk).is_err() {
+                    return;
+                }
+            }
+        });
+        prefetched.insert(name, receiver);
+    }
+}
+
+/// The prefetched entries of the location, read until the end even if the prefetch is still running.
+/// They are only used once, showing the location again reads it anew.
+pub fn take(location_name: &str) -> Option<impl Read> {
+    let receiver = PREFETCHED.lock().unwrap().as_mut()?.remove(location_name)?;
+    debug!("Showing prefetched entries of {}", location_name);
+    Some(ChannelReader { receiver, chunk: Vec::new(), pos: 0 })
+}
+
+struct ChannelReader {
+    receiver: Chunks,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => (self.chunk, self.pos) = (chunk?, 0),
+                // the prefetch has finished
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[test]
+fn channel_reader() {
+    let (sender, receiver) = mpsc::sync_channel(4);
+    sender.send(Ok(b"a.pdf\nb".to_vec())).unwrap();
+    sender.send(Ok(b".txt\n".to_vec())).unwrap();
+    drop(sender);
+    let mut entries = String::new();
+    ChannelReader { receiver, chunk: Vec::new(), pos: 0 }.read_to_string(&mut entries).unwrap();
+    assert_eq!(entries, "a.pdf\nb.txt\n");
+}
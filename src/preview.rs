@@ -0,0 +1,52 @@
+use std::{fs, io::{self, Write}, path::Path, process::{Command, Stdio}};
+use anyhow::Result;
+use log::debug;
+use blink_search::{find_program, WithFunction};
+
+/// The programs listing a folder in the preview, tried in order.
+#[cfg(not(windows))]
+const LISTERS: &[&[&str]] = &[
+    &["eza", "-1", "--color=always", "--group-directories-first"],
+    &["ls", "-1A", "--color=always"],
+];
+#[cfg(windows)]
+const LISTERS: &[&[&str]] = &[
+    &["eza", "-1", "--color=always", "--group-directories-first"],
+    &["cmd", "/C", "dir", "/B"],
+];
+
+/// Lists the folder like `ls`, for systems without any of the [`LISTERS`]. Folders come first and end with a slash.
+fn list_folder(path: &Path, out: &mut impl Write) -> Result<()> {
+    let mut entries: Vec<(bool, String)> = fs::read_dir(path)?
+        .filter_map(|e| e.ok())
+        .map(|e| (!e.path().is_dir(), e.file_name().to_string_lossy().into_owned()))
+        .collect();
+    entries.sort();
+    for (is_file, name) in entries {
+        writeln!(out, "{}{}", name, if is_file { "" } else { "/" })?;
+    }
+    Ok(())
+}
+
+/// Prints the contents of the folder for the fzf preview of folder locations.
+pub fn print_folder(path: &Path) -> Result<()> {
+    if !path.is_dir() {
+        println!("{} is no folder", path.to_string_lossy());
+        return Ok(());
+    }
+    for lister in LISTERS {
+        let Some(program) = find_program(lister[0]) else {
+            continue;
+        };
+        let status = Command::new(program)
+            .args(&lister[1..])
+            .arg(path)
+            .stdin(Stdio::null())
+            .with(|b| debug!("Executing: {:?}", b))
+            .status();
+        if status.is_ok_and(|s| s.success()) {
+            return Ok(());
+        }
+    }
+    list_folder(path, &mut io::stdout().lock())
+}
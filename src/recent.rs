@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use log::debug;
+use crate::config::{Config, Location, LocationMode};
+
+pub const LOCATION_NAME: &str = "recent";
+const DEFAULT_SIZE: usize = 100;
+
+pub fn path() -> PathBuf {
+    Config::base_dir().join("recent.txt")
+}
+
+/// The `recent` pseudo-location. Its entries are absolute paths, read from the list like a cache file.
+pub fn location() -> Result<Location> {
+    if !path().exists() {
+        std::fs::write(path(), "")?;
+    }
+    Ok(Location {
+        path: String::new(),
+        mode: LocationMode::Files,
+        cache_file: Some(path().to_string_lossy().into_owned()),
+        ..Default::default()
+    })
+}
+
+/// Whether the path is in the recently opened list.
+pub fn contains(path: &Path) -> bool {
+    let entry = path.to_string_lossy();
+    std::fs::read_to_string(self::path()).unwrap_or_default().lines().any(|l| l == entry)
+}
+
+/// Moves the path to the top of the recently opened list.
+pub fn record(path: &Path, config: &Config) -> Result<()> {
+    let size = config.recent_size.unwrap_or(DEFAULT_SIZE);
+    if size == 0 {
+        return Ok(());
+    }
+    let entry = path.to_string_lossy();
+    debug!("Recording recently opened: {}", entry);
+    let existing = std::fs::read_to_string(self::path()).unwrap_or_default();
+    let entries: Vec<&str> = std::iter::once(entry.as_ref())
+        .chain(existing.lines().filter(|l| *l != entry))
+        .take(size)
+        .collect();
+    std::fs::write(self::path(), entries.join("\n") + "\n")?;
+    Ok(())
+}
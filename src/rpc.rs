@@ -0,0 +1,184 @@
+use std::{io::{self, BufRead, BufReader, Write}, path::{Component, Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Mutex}, thread};
+use anyhow::Result;
+use log::debug;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use blink_search::config::Config;
+use blink_search::error::BlinkError;
+use blink_search::index::{entry_path, read_location};
+use blink_search::{paths, recent, search, Opener};
+use crate::daemon::Match;
+
+/// How many matches one `matches` notification holds at most.
+const BATCH_SIZE: usize = 100;
+const DEFAULT_LIMIT: usize = 50;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_FAILED: i64 = -32000;
+/// The code of searches cancelled by a newer one, as in the Language Server Protocol.
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// A JSON-RPC request. Requests without an id are notifications, which get no response.
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    location: String,
+    #[serde(default)]
+    query: String,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenParams {
+    location: String,
+    entry: String,
+}
+
+/// Opens an entry of the location, like selecting it in the search, and returns its path.
+/// Only entries inside the location can be opened, and of `recent` only the paths in its list.
+pub fn open_entry(location_name: &str, entry: &str, config: &Config) -> Result<PathBuf> {
+    let location = config.locations.get(location_name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown location {}", location_name))?;
+    let allowed = match location_name == recent::LOCATION_NAME {
+        true => recent::contains(Path::new(entry)),
+        false => Path::new(entry).components().all(|c| matches!(c, Component::Normal(_))),
+    };
+    if !allowed {
+        return Err(anyhow::anyhow!("Entry {} is outside of {}", entry, location_name));
+    }
+    let path = entry_path(location, Path::new(entry), config);
+    Opener::new(config).open_entry(&path, location)?;
+    Ok(path)
+}
+
+/// Writes the responses and notifications, one line each.
+struct Output(Mutex<io::Stdout>);
+
+impl Output {
+    fn send(&self, message: Value) {
+        let mut out = self.0.lock().unwrap();
+        // a closed stdout ends the requests as well
+        let _ = writeln!(out, "{}", message).and_then(|_| out.flush());
+    }
+
+    fn result(&self, id: Value, result: Value) {
+        if !id.is_null() {
+            self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        }
+    }
+
+    fn error(&self, id: Value, code: i64, message: impl ToString) {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message.to_string() } }));
+    }
+}
+
+/// Sends the matches in `matches` notifications while the location is read, up to the limit, and returns
+/// the best matches. Returns `None` if a newer search started meanwhile.
+fn search(id: &Value, params: &SearchParams, config: &Config, out: &Output, cancelled: impl Fn() -> bool) -> Result<Option<Value>> {
+    let name = config.find_location(&params.location).ok_or_else(|| BlinkError::LocationNotFound(params.location.clone()))?;
+    let location = &config.locations[name];
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let to_match = |(entry, score): &(PathBuf, i64)| Match {
+        entry: entry.to_string_lossy().into_owned(),
+        path: entry_path(location, entry, config).to_string_lossy().into_owned(),
+        score: *score,
+    };
+    let mut results = Vec::new();
+    let mut batch = Vec::new();
+    for entry in BufReader::new(read_location(name, location, config)?).split(b'\n') {
+        if cancelled() {
+            return Ok(None);
+        }
+        let entry = paths::from_bytes(entry?);
+        let Some(score) = search::score(&entry.to_string_lossy(), &params.query) else { continue };
+        results.push((entry, score));
+        if results.len() <= limit {
+            batch.push(to_match(results.last().unwrap()));
+        }
+        if batch.len() == BATCH_SIZE || (results.len() == limit && !batch.is_empty()) {
+            out.send(json!({ "jsonrpc": "2.0", "method": "matches", "params": { "id": id, "matches": std::mem::take(&mut batch) } }));
+        }
+    }
+    if !batch.is_empty() {
+        out.send(json!({ "jsonrpc": "2.0", "method": "matches", "params": { "id": id, "matches": batch } }));
+    }
+    search::keep_best(&mut results, limit);
+    Ok(Some(json!(results.iter().map(to_match).collect::<Vec<Match>>())))
+}
+
+/// Answers JSON-RPC 2.0 requests read from stdin, one per line, until stdin is closed:
+/// `locations`, `search` with `{"location", "query", "limit"}` and `open` with `{"location", "entry"}`.
+/// A search cancels the one still running.
+pub fn serve(config: &Config) -> Result<()> {
+    let out = Output(Mutex::new(io::stdout()));
+    let latest_search = AtomicU64::new(0);
+    thread::scope(|s| {
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    out.error(Value::Null, PARSE_ERROR, e);
+                    continue;
+                },
+            };
+            debug!("RPC {} {}", request.method, request.params);
+            match request.method.as_str() {
+                "locations" => out.result(request.id, Value::Array(config.enabled_locations()
+                    .map(|(name, loc)| json!({ "name": name, "path": loc.path, "mode": loc.mode.to_string() }))
+                    .collect())),
+                "search" => match serde_json::from_value::<SearchParams>(request.params) {
+                    Ok(params) => {
+                        let search_id = latest_search.fetch_add(1, Ordering::SeqCst) + 1;
+                        let (out, latest_search) = (&out, &latest_search);
+                        s.spawn(move || {
+                            let cancelled = || latest_search.load(Ordering::SeqCst) != search_id;
+                            match search(&request.id, &params, config, out, cancelled) {
+                                Ok(Some(matches)) => out.result(request.id, matches),
+                                Ok(None) => out.error(request.id, REQUEST_CANCELLED, "cancelled by a newer search"),
+                                Err(e) => out.error(request.id, REQUEST_FAILED, e),
+                            }
+                        });
+                    },
+                    Err(e) => out.error(request.id, INVALID_PARAMS, e),
+                },
+                "open" => match serde_json::from_value::<OpenParams>(request.params) {
+                    Ok(params) => match open_entry(&params.location, &params.entry, config) {
+                        Ok(path) => out.result(request.id, json!({ "opened": path.to_string_lossy() })),
+                        Err(e) => out.error(request.id, REQUEST_FAILED, e),
+                    },
+                    Err(e) => out.error(request.id, INVALID_PARAMS, e),
+                },
+                method => out.error(request.id, METHOD_NOT_FOUND, format!("Unknown method {}", method)),
+            }
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn entries_outside_locations() {
+    let mut config = blink_search::config::ConfigFormat::Yaml.parse("
+locations:
+  docs: { path: /docs, mode: files }
+").unwrap();
+    config.locations.insert(recent::LOCATION_NAME.to_owned(), Default::default());
+    assert!(open_entry("docs", "../etc/passwd", &config).is_err());
+    // recent only opens what was opened before
+    assert!(open_entry(recent::LOCATION_NAME, "/nonexistent/blink/passwd", &config).is_err());
+    assert!(open_entry("docs", "/etc/passwd", &config).is_err());
+    assert!(open_entry("nas", "a.txt", &config).is_err());
+}
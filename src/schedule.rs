@@ -0,0 +1,119 @@
+use std::{env, io::Write, process::{Command, Stdio}};
+use anyhow::Result;
+use log::debug;
+use blink_search::WithFunction;
+
+const CRON_MARKER: &str = "# blink-search cache update";
+const TASK_NAME: &str = "blink-search cache update";
+
+/// Registers `--update-cache all` to run on the given cron schedule (e.g. `0 3 * * *`).
+pub fn install(schedule: &str) -> Result<()> {
+    let exe = env::current_exe()?;
+    if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("schtasks");
+        cmd
+            .arg("/Create").arg("/F")
+            .arg("/TN").arg(TASK_NAME)
+            .arg("/TR").arg(format!("\"{}\" --update-cache all", exe.display()))
+            .args(cron_to_schtasks(schedule)?);
+        run_checked(&mut cmd)
+    } else {
+        if schedule.split_whitespace().count() != 5 {
+            return Err(anyhow::anyhow!("Invalid cron schedule \"{}\", expected five fields like \"0 3 * * *\"", schedule));
+        }
+        let mut lines = read_crontab()?;
+        lines.push(format!("{} \"{}\" --update-cache all {}", schedule, exe.display(), CRON_MARKER));
+        write_crontab(&lines)
+    }
+}
+
+pub fn uninstall() -> Result<()> {
+    if cfg!(target_os = "windows") {
+        run_checked(Command::new("schtasks").arg("/Delete").arg("/F").arg("/TN").arg(TASK_NAME))
+    } else {
+        write_crontab(&read_crontab()?)
+    }
+}
+
+/// Returns the current crontab without blink's entry.
+fn read_crontab() -> Result<Vec<String>> {
+    let out = Command::new("crontab")
+        .arg("-l")
+        .stderr(Stdio::null())
+        .with(|b| debug!("Executing: {:?}", b))
+        .output()?;
+    // crontab -l fails if the user has no crontab yet
+    let crontab = if out.status.success() { String::from_utf8_lossy(&out.stdout).into_owned() } else { String::new() };
+    Ok(crontab.lines()
+        .filter(|l| !l.ends_with(CRON_MARKER))
+        .map(|l| l.to_owned())
+        .collect())
+}
+
+fn write_crontab(lines: &[String]) -> Result<()> {
+    let mut crontab = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .with(|b| debug!("Executing: {:?}", b))
+        .spawn()?;
+    for line in lines {
+        writeln!(crontab.stdin.as_ref().unwrap(), "{}", line)?;
+    }
+    drop(crontab.stdin.take());
+    let status = crontab.wait()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("crontab exited with {}", status));
+    }
+    Ok(())
+}
+
+fn run_checked(cmd: &mut Command) -> Result<()> {
+    let status = cmd
+        .with(|b| debug!("Executing: {:?}", b))
+        .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("{:?} exited with {}", cmd.get_program(), status));
+    }
+    Ok(())
+}
+
+/// Task Scheduler has no cron syntax, so only hourly, daily and weekly schedules are translated.
+fn cron_to_schtasks(schedule: &str) -> Result<Vec<String>> {
+    let invalid = || anyhow::anyhow!("Unsupported schedule \"{}\" for Task Scheduler. Use \"M * * * *\", \"M H * * *\" or \"M H * * D\"", schedule);
+    let fields: Vec<&str> = schedule.split_whitespace().collect();
+    let [minute, hour, "*", "*", weekday] = fields[..] else { return Err(invalid()) };
+    let minute: u32 = minute.parse().map_err(|_| invalid())?;
+    if minute > 59 {
+        return Err(invalid());
+    }
+
+    let args: Vec<String> = match (hour, weekday) {
+        ("*", "*") => vec!["/SC".into(), "HOURLY".into(), "/ST".into(), format!("00:{:02}", minute)],
+        (hour, weekday) => {
+            let hour: u32 = hour.parse().map_err(|_| invalid())?;
+            if hour > 23 {
+                return Err(invalid());
+            }
+            let start = format!("{:02}:{:02}", hour, minute);
+            match weekday {
+                "*" => vec!["/SC".into(), "DAILY".into(), "/ST".into(), start],
+                d => {
+                    let day = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"]
+                        .get(d.parse::<usize>().map_err(|_| invalid())?)
+                        .ok_or_else(invalid)?;
+                    vec!["/SC".into(), "WEEKLY".into(), "/D".into(), day.to_string(), "/ST".into(), start]
+                }
+            }
+        }
+    };
+    Ok(args)
+}
+
+#[test]
+fn cron_schedules_for_task_scheduler() {
+    assert_eq!(cron_to_schtasks("0 3 * * *").unwrap(), ["/SC", "DAILY", "/ST", "03:00"]);
+    assert_eq!(cron_to_schtasks("30 * * * *").unwrap(), ["/SC", "HOURLY", "/ST", "00:30"]);
+    assert_eq!(cron_to_schtasks("15 22 * * 1").unwrap(), ["/SC", "WEEKLY", "/D", "MON", "/ST", "22:15"]);
+    assert!(cron_to_schtasks("0 3 1 * *").is_err());
+    assert!(cron_to_schtasks("*/5 * * * *").is_err());
+}
@@ -0,0 +1,138 @@
+use std::{io::{self, BufRead, BufReader, PipeReader, Write}, path::{Path, PathBuf}, thread};
+use anyhow::Result;
+use log::{debug, error};
+use crate::config::Config;
+
+/// The hooks of the rhai `script` in the config. Each function is optional:
+///
+/// - `transform_entry(entry)` returns the entry to show instead, or `()` to hide it
+/// - `on_select(entry)` returns the chosen entry to open instead
+/// - `on_open(path)` returns the path to open instead, or `false` to not open it
+#[cfg(feature = "scripting")]
+pub struct Hooks {
+    engine: rhai::Engine,
+    ast: rhai::AST,
+}
+
+#[cfg(feature = "scripting")]
+impl Hooks {
+    /// Compiles the `script` of the config, if there is one.
+    pub fn load(config: &Config) -> Result<Option<Hooks>> {
+        let Some(script) = &config.script else {
+            return Ok(None);
+        };
+        let engine = rhai::Engine::new();
+        let ast = engine.compile_file(Config::base_dir().join(script))
+            .map_err(|e| anyhow::anyhow!("Failed to load script {}: {}", script, e))?;
+        Ok(Some(Hooks { engine, ast }))
+    }
+
+    /// Whether the script defines the hook.
+    pub fn has(&self, hook: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == hook && f.params.len() == 1)
+    }
+
+    /// Calls the hook with the string, or returns `None` if the script does not define it.
+    fn call(&self, hook: &str, arg: &str) -> Result<Option<rhai::Dynamic>> {
+        if !self.has(hook) {
+            return Ok(None);
+        }
+        let result = self.engine.call_fn::<rhai::Dynamic>(&mut rhai::Scope::new(), &self.ast, hook, (arg.to_owned(),))
+            .map_err(|e| anyhow::anyhow!("Script hook {} failed: {}", hook, e))?;
+        debug!("{}({:?}) = {:?}", hook, arg, result);
+        Ok(Some(result))
+    }
+
+    fn to_string(hook: &str, value: rhai::Dynamic) -> Result<String> {
+        value.into_string().map_err(|t| anyhow::anyhow!("Script hook {} returned {} instead of a string", hook, t))
+    }
+
+    /// The entry as shown in the picker, or `None` if it is hidden.
+    pub fn transform_entry(&self, entry: &str) -> Result<Option<String>> {
+        match self.call("transform_entry", entry)? {
+            None => Ok(Some(entry.to_owned())),
+            Some(value) if value.is_unit() => Ok(None),
+            Some(value) => Self::to_string("transform_entry", value).map(Some),
+        }
+    }
+
+    /// The entry to open for the one chosen in the picker.
+    pub fn on_select(&self, entry: &Path) -> Result<PathBuf> {
+        match self.call("on_select", &entry.to_string_lossy())? {
+            None => Ok(entry.to_owned()),
+            Some(value) => Self::to_string("on_select", value).map(PathBuf::from),
+        }
+    }
+
+    /// The path to open, or `None` if the script vetoes opening it.
+    pub fn on_open(&self, path: &Path) -> Result<Option<PathBuf>> {
+        match self.call("on_open", &path.to_string_lossy())? {
+            Some(value) if value.as_bool() == Ok(false) => Ok(None),
+            Some(value) if value.is_string() => Self::to_string("on_open", value).map(|p| Some(PathBuf::from(p))),
+            _ => Ok(Some(path.to_owned())),
+        }
+    }
+}
+
+/// Without the `scripting` feature, a `script` in the config is an error and there are no hooks.
+#[cfg(not(feature = "scripting"))]
+pub struct Hooks;
+
+#[cfg(not(feature = "scripting"))]
+impl Hooks {
+    pub fn load(config: &Config) -> Result<Option<Hooks>> {
+        match config.script {
+            Some(_) => Err(anyhow::anyhow!("blink was built without scripting. Rebuild with `--features scripting`")),
+            None => Ok(None),
+        }
+    }
+
+    pub fn has(&self, _hook: &str) -> bool {
+        false
+    }
+
+    pub fn transform_entry(&self, entry: &str) -> Result<Option<String>> {
+        Ok(Some(entry.to_owned()))
+    }
+
+    pub fn on_select(&self, entry: &Path) -> Result<PathBuf> {
+        Ok(entry.to_owned())
+    }
+
+    pub fn on_open(&self, path: &Path) -> Result<Option<PathBuf>> {
+        Ok(Some(path.to_owned()))
+    }
+}
+
+/// Passes the entries through the `transform_entry` hook in a background thread, if the script has one.
+pub fn transform_entries(list: PipeReader, config: &Config) -> Result<PipeReader> {
+    let Some(hooks) = Hooks::load(config)?.filter(|h| h.has("transform_entry")) else {
+        return Ok(list);
+    };
+    let (reader, mut writer) = io::pipe()?;
+    thread::spawn(move || {
+        let transform = || -> Result<()> {
+            for entry in BufReader::new(list).split(b'\n') {
+                let entry = entry?;
+                // the script only sees text, other entries are passed on as they are to keep their paths
+                let Ok(text) = std::str::from_utf8(&entry) else {
+                    writer.write_all(&entry)?;
+                    writer.write_all(b"\n")?;
+                    continue;
+                };
+                if let Some(entry) = hooks.transform_entry(text)? {
+                    writeln!(writer, "{}", entry)?;
+                }
+            }
+            Ok(())
+        };
+        match transform() {
+            // the picker closing the pipe early is expected
+            Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+                debug!("Stopped transforming entries: {}", e),
+            Err(e) => error!("Failed to transform entries: {}", e),
+            Ok(()) => (),
+        }
+    });
+    Ok(reader)
+}
@@ -0,0 +1,74 @@
+use std::{io::{BufRead, BufReader}, path::PathBuf};
+use anyhow::Result;
+use crate::config::{Config, Location};
+use crate::index::read_location;
+use crate::paths;
+
+/// Scores an entry for a query of whitespace-separated words, which all have to be contained ignoring case.
+/// Matches in the file name and short entries rank higher.
+pub fn score(entry: &str, query: &str) -> Option<i64> {
+    let entry = entry.to_lowercase();
+    let file_name = entry.rsplit(['/', '\\']).next().unwrap_or(&entry);
+    let mut score = 1000 - entry.len().min(1000) as i64;
+    for word in query.split_whitespace().map(|w| w.to_lowercase()) {
+        match (file_name.contains(&word), entry.contains(&word)) {
+            (true, _) => score += 1000,
+            (false, true) => (),
+            (false, false) => return None,
+        }
+    }
+    Some(score)
+}
+
+/// The best `limit` entries of the location for the query, with their scores.
+pub fn search(location_name: &str, location: &Location, config: &Config, query: &str, limit: usize) -> Result<Vec<(PathBuf, i64)>> {
+    let mut results = Vec::new();
+    for entry in BufReader::new(read_location(location_name, location, config)?).split(b'\n') {
+        let entry = paths::from_bytes(entry?);
+        if let Some(score) = score(&entry.to_string_lossy(), query) {
+            results.push((entry, score));
+        }
+    }
+    keep_best(&mut results, limit);
+    Ok(results)
+}
+
+/// Like [`search`], for entries kept in memory, e.g. by `bl daemon`.
+pub fn search_entries(entries: &[PathBuf], query: &str, limit: usize) -> Vec<(PathBuf, i64)> {
+    let mut results: Vec<(PathBuf, i64)> = entries.iter()
+        .filter_map(|entry| score(&entry.to_string_lossy(), query).map(|score| (entry.clone(), score)))
+        .collect();
+    keep_best(&mut results, limit);
+    results
+}
+
+/// Sorts the results by score, best first, and keeps `limit` of them.
+pub fn keep_best(results: &mut Vec<(PathBuf, i64)>, limit: usize) {
+    // stable, so equal scores keep the order of the list
+    results.sort_by_key(|(_, score)| -score);
+    results.truncate(limit);
+}
+
+/// The best entry for each query, reading the location only once. Queries without a match are skipped.
+pub fn best_matches(location_name: &str, location: &Location, config: &Config, queries: &[String]) -> Result<Vec<PathBuf>> {
+    let mut best: Vec<Option<(PathBuf, i64)>> = vec![None; queries.len()];
+    for entry in BufReader::new(read_location(location_name, location, config)?).split(b'\n') {
+        let entry = paths::from_bytes(entry?);
+        let name = entry.to_string_lossy();
+        for (query, best) in queries.iter().zip(best.iter_mut()) {
+            match score(&name, query) {
+                Some(score) if best.as_ref().is_none_or(|(_, s)| score > *s) => *best = Some((entry.clone(), score)),
+                _ => (),
+            }
+        }
+    }
+    Ok(best.into_iter().flatten().map(|(entry, _)| entry).collect())
+}
+
+#[test]
+fn query_scoring() {
+    assert!(score("Projects/2024/Report.pdf", "report 2024").is_some());
+    assert!(score("anything", "").is_some());
+    assert_eq!(score("Projects/2024/Report.pdf", "report 2023"), None);
+    assert!(score("old/report.pdf", "report") > score("report/old.pdf", "report"));
+}
@@ -0,0 +1,81 @@
+use std::{io::{self, IsTerminal}, path::PathBuf};
+use anyhow::Result;
+use directories::BaseDirs;
+use log::debug;
+use blink_search::config::{Config, ConfigFormat, Location, LocationMode};
+use blink_search::error::BlinkError;
+use blink_search::index::read_location_live;
+use blink_search::paths;
+use crate::picker::{self, Choice, Entries, Pick};
+
+/// How deep below the home folder folders are offered for the first location.
+const FOLDER_DEPTH: usize = 3;
+
+fn prompt(question: &str, default: &str) -> Result<String> {
+    eprint!("{} [{}]: ", question, default);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim() {
+        "" => default.to_owned(),
+        answer => answer.to_owned(),
+    })
+}
+
+/// Lets the folder be chosen in the picker among those below the home folder, or else typed in.
+fn choose_folder(config: &Config, home: PathBuf) -> Result<PathBuf> {
+    let location = Location {
+        path: home.to_string_lossy().into_owned(),
+        mode: LocationMode::Folders,
+        max_depth: Some(FOLDER_DEPTH),
+        ..Default::default()
+    };
+    let (mut list, _watchdog) = read_location_live(&location, config)?;
+    let entries: Entries = Box::new(move |out| {
+        writeln!(out, ".")?;
+        io::copy(&mut list, out)?;
+        Ok(())
+    });
+    match picker::for_config(config).pick(Pick::Folder, entries) {
+        Ok(Choice::Entry(entry)) => Ok(home.join(paths::from_bytes(entry))),
+        Err(e) if e.downcast_ref::<BlinkError>().is_some_and(|e| matches!(e, BlinkError::Aborted)) => {
+            Ok(PathBuf::from(prompt("Path of the location", &home.to_string_lossy())?))
+        },
+        Err(e) => Err(e),
+        Ok(_) => Err(BlinkError::Aborted.into()),
+    }
+}
+
+/// Asks for a first location and adds it to the config file. Returns false if there is no terminal to ask on,
+/// or no location was added.
+pub fn run(config: &mut Config) -> Result<bool> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Ok(false);
+    }
+    eprintln!("No locations defined yet. Let's set up the first one.");
+    if prompt("Set up a location now? (y/n)", "y")?.to_lowercase() != "y" {
+        return Ok(false);
+    }
+    let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_owned()).unwrap_or_default();
+    let name = prompt("Name of the location", "home")?;
+    eprintln!("Choose its folder, esc to type it instead");
+    let path = paths::native_path(&choose_folder(config, home)?);
+    if !path.is_dir() {
+        return Err(anyhow::anyhow!("{} is no folder", path.to_string_lossy()));
+    }
+    let mode = match prompt("Search files, folders or all?", "files")?.as_str() {
+        "folders" => LocationMode::Folders,
+        "all" => LocationMode::All,
+        _ => LocationMode::Files,
+    };
+
+    config.locations.insert(name.clone(), Location {
+        path: path.to_string_lossy().into_owned(),
+        mode,
+        ..Default::default()
+    });
+    let config_path = Config::path();
+    debug!("Adding location {} to {}", name, config_path.to_string_lossy());
+    std::fs::write(&config_path, ConfigFormat::from_path(&config_path).serialize(config)?)?;
+    eprintln!("Added {} to {}", name, config_path.to_string_lossy());
+    Ok(true)
+}
@@ -0,0 +1,70 @@
+use std::{io::{BufRead, BufReader, Read}, path::Path, time::Duration};
+use anyhow::Result;
+use blink_search::config::{Config, Location, LocationMode};
+use blink_search::index::{read_location_from_cache, read_location_live};
+use blink_search::{cache, finder, history, recent};
+use crate::doctor::file_size;
+
+/// The number of lines of the list.
+pub fn count_lines(list: impl Read) -> Result<usize> {
+    let mut count = 0;
+    for entry in BufReader::new(list).split(b'\n') {
+        entry?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The number of entries and where they were counted: the cache file, or a scan of the location.
+fn count_entries(location: &Location, config: &Config, available: bool) -> Result<(Option<usize>, &'static str)> {
+    if let Some(path) = cache::cache_path(location).filter(|p| p.exists()) {
+        return Ok((Some(count_lines(read_location_from_cache(path)?)?), "cache"));
+    }
+    // commands may query remote services, so only folders are scanned
+    if !available || location.list_command.is_some() || !matches!(location.mode, LocationMode::Files | LocationMode::Folders | LocationMode::All) {
+        return Ok((None, "-"));
+    }
+    let (list, watchdog) = read_location_live(location, config)?;
+    let count = count_lines(list)?;
+    Ok(match watchdog.timed_out() {
+        true => (None, "timeout"),
+        false => (Some(count), "scan"),
+    })
+}
+
+/// Prints the entries, cache, history and recently opened entries of each enabled location.
+pub fn print(config: &Config) -> Result<()> {
+    let locations: Vec<(&String, &Location)> = config.enabled_locations().collect();
+    let available = finder::available(locations.iter().map(|(_, loc)| *loc));
+    let recent = std::fs::read_to_string(recent::path()).unwrap_or_default();
+
+    println!("{:<20} {:>10} {:>7} {:>10} {:>12} {:>8} {:>8}", "location", "entries", "from", "cache", "cache age", "history", "recent");
+    for ((name, location), available) in locations.into_iter().zip(available) {
+        let (entries, source) = count_entries(location, config, available)?;
+        let cache = cache::cache_path(location).filter(|p| p.exists());
+        let size = cache.as_ref().and_then(|p| p.metadata().ok()).map(|m| file_size(m.len()));
+        let age = cache.as_ref().and_then(|p| cache::cache_age(p))
+            // seconds are only shown for fresh caches
+            .map(|age| match age.as_secs() {
+                secs if secs < 60 => Duration::from_secs(secs),
+                secs => Duration::from_secs(secs / 60 * 60),
+            })
+            .map(|age| humantime::format_duration(age).to_string());
+        let queries = std::fs::read_to_string(history::path(name)?).unwrap_or_default()
+            .lines().filter(|l| !l.is_empty()).count();
+        // blink keeps no open counts, the recent list holds each opened path once
+        let opened = match location.path.is_empty() {
+            true => 0,
+            false => recent.lines().filter(|l| Path::new(l).starts_with(&location.path)).count(),
+        };
+        println!("{:<20} {:>10} {:>7} {:>10} {:>12} {:>8} {:>8}",
+            name,
+            entries.map_or("-".to_owned(), |n| n.to_string()),
+            source,
+            size.as_deref().unwrap_or("-"),
+            age.as_deref().unwrap_or("-"),
+            queries,
+            opened);
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use blink_search::config::Config;
+
+/// What the entries of the tray menu do.
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+enum TrayAction {
+    Location(String),
+    UpdateAll,
+    EditConfig,
+    Quit,
+}
+
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn menu_entries(config: &Config) -> Vec<Option<(String, TrayAction)>> {
+    config.enabled_locations()
+        .map(|(name, _)| Some((name.clone(), TrayAction::Location(name.clone()))))
+        .chain([
+            None,
+            Some(("Update all caches".to_owned(), TrayAction::UpdateAll)),
+            Some(("Edit config".to_owned(), TrayAction::EditConfig)),
+            None,
+            Some(("Quit".to_owned(), TrayAction::Quit)),
+        ])
+        .collect()
+}
+
+#[cfg_attr(not(feature = "tray"), allow(dead_code))]
+fn run_action(action: &TrayAction, config: &Config) -> Result<()> {
+    match action {
+        TrayAction::Location(name) => crate::hotkey::launch(config, Some(name)),
+        TrayAction::UpdateAll => {
+            std::process::Command::new(std::env::current_exe()?)
+                .arg("--update-cache").arg("all")
+                .spawn()?;
+            Ok(())
+        },
+        TrayAction::EditConfig => blink_search::open::open_folder(&Config::path(), config),
+        TrayAction::Quit => std::process::exit(0),
+    }
+}
+
+/// Shows the tray icon until Quit is chosen.
+#[cfg(all(feature = "tray", target_os = "linux"))]
+pub fn run(config: Config) -> Result<()> {
+    use ksni::blocking::TrayMethods;
+
+    struct BlinkTray {
+        config: Config,
+    }
+
+    impl ksni::Tray for BlinkTray {
+        fn id(&self) -> String {
+            env!("CARGO_PKG_NAME").into()
+        }
+        fn title(&self) -> String {
+            "blink".into()
+        }
+        fn icon_name(&self) -> String {
+            "system-search".into()
+        }
+        fn activate(&mut self, _x: i32, _y: i32) {
+            if let Err(e) = crate::hotkey::launch(&self.config, None) {
+                log::error!("Failed to launch blink: {}", e);
+            }
+        }
+        fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+            menu_entries(&self.config).into_iter()
+                .map(|entry| match entry {
+                    Some((label, action)) => ksni::menu::StandardItem {
+                        label,
+                        activate: Box::new(move |tray: &mut Self| if let Err(e) = run_action(&action, &tray.config) {
+                            log::error!("Tray action failed: {}", e);
+                        }),
+                        ..Default::default()
+                    }.into(),
+                    None => ksni::MenuItem::Separator,
+                })
+                .collect()
+        }
+    }
+
+    let _handle = BlinkTray { config }.spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to show the tray icon: {}", e))?;
+    crate::hotkey::wait_for_events();
+    Ok(())
+}
+
+/// Shows the tray icon until Quit is chosen.
+#[cfg(all(feature = "tray", target_os = "windows"))]
+pub fn run(config: Config) -> Result<()> {
+    use tray_icon::{menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem}, Icon, TrayIconBuilder};
+
+    let menu = Menu::new();
+    let mut actions = Vec::new();
+    for entry in menu_entries(&config) {
+        match entry {
+            Some((label, action)) => {
+                let id = actions.len().to_string();
+                menu.append(&MenuItem::with_id(id, label, true, None))?;
+                actions.push(action);
+            },
+            None => menu.append(&PredefinedMenuItem::separator())?,
+        }
+    }
+
+    // a plain blue square, to not depend on icon files next to the executable
+    let icon = Icon::from_rgba([0x1e, 0x6f, 0xd9, 0xff].repeat(16 * 16), 16, 16)?;
+    let _tray = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("blink")
+        .with_icon(icon)
+        .build()?;
+
+    MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+        let action = event.id.0.parse::<usize>().ok().and_then(|i| actions.get(i));
+        if let Some(Err(e)) = action.map(|a| run_action(a, &config)) {
+            log::error!("Tray action failed: {}", e);
+        }
+    }));
+    crate::hotkey::wait_for_events();
+    Ok(())
+}
+
+#[cfg(not(all(feature = "tray", any(target_os = "linux", target_os = "windows"))))]
+pub fn run(_config: Config) -> Result<()> {
+    Err(anyhow::anyhow!("blink was built without tray support. Rebuild with `--features tray` on Windows or Linux"))
+}
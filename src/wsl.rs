@@ -0,0 +1,53 @@
+use std::{path::Path, process::Command, sync::OnceLock};
+use anyhow::Result;
+use log::debug;
+use crate::WithFunction;
+
+/// Whether blink runs inside the Windows Subsystem for Linux.
+pub fn is_wsl() -> bool {
+    static IS_WSL: OnceLock<bool> = OnceLock::new();
+    *IS_WSL.get_or_init(|| {
+        cfg!(target_os = "linux") && (
+            std::env::var_os("WSL_DISTRO_NAME").is_some() ||
+            std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .is_ok_and(|r| r.to_lowercase().contains("microsoft"))
+        )
+    })
+}
+
+/// Paths like `C:\Users` or `\\server\share`, which need translation to be used inside WSL.
+pub fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    path.starts_with("\\\\") || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+}
+
+fn wslpath(flag: &str, path: &str) -> Result<String> {
+    let out = Command::new("wslpath")
+        .arg(flag)
+        .arg(path)
+        .with(|b| debug!("Executing: {:?}", b))
+        .output()?;
+    if !out.status.success() {
+        return Err(anyhow::anyhow!("wslpath {} {} failed: {}", flag, path, String::from_utf8_lossy(&out.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Translates a Linux path like `/mnt/c/Users` into `C:\Users` for Windows programs.
+pub fn to_windows(path: &Path) -> Result<String> {
+    wslpath("-w", &path.to_string_lossy())
+}
+
+/// Translates a Windows path like `C:\Users` into `/mnt/c/Users`.
+pub fn to_linux(path: &str) -> Result<String> {
+    wslpath("-u", path)
+}
+
+#[test]
+fn detects_windows_paths() {
+    assert!(is_windows_path("C:\\Users\\me"));
+    assert!(is_windows_path("d:/data"));
+    assert!(is_windows_path("\\\\nas.local\\share"));
+    assert!(!is_windows_path("/mnt/c/Users"));
+    assert!(!is_windows_path("relative/path"));
+}